@@ -0,0 +1,308 @@
+use core::cmp::{max, min};
+
+/// Split of tested memory into equally sized windows grouped into allocations. Test offsets are
+/// virtual: allocations follow each other without gaps in the order of creation, so the windows
+/// are numbered the same way as with a single huge allocation.
+#[derive(Copy, Clone, Debug)]
+pub struct AllocationLayout {
+    pub window_size: i64,
+    pub window_count: i64,
+    pub windows_per_allocation: i64,
+}
+
+impl AllocationLayout {
+    /// Returns None if the total size can't hold windows of at least `granularity` bytes
+    pub fn plan(
+        total_size: i64,
+        max_allocation_size: i64,
+        max_window_size: i64,
+        granularity: i64,
+    ) -> Option<Self> {
+        let mut max_window_size = min(max_window_size, max_allocation_size);
+        max_window_size -= max_window_size % granularity;
+        if max_window_size <= 0 {
+            return None;
+        }
+        let mut window_count =
+            total_size / max_window_size + i64::from(total_size % max_window_size != 0);
+        window_count = max(window_count, 2); //at least 2 windows: for testing rereads and rws
+        let mut window_size = total_size / window_count;
+        window_size -= window_size % granularity;
+        if window_size <= 0 {
+            return None;
+        }
+        Some(Self {
+            window_size,
+            window_count,
+            windows_per_allocation: max(max_allocation_size / window_size, 1),
+        })
+    }
+
+    pub fn total_size(&self) -> i64 {
+        self.window_size * self.window_count
+    }
+
+    pub fn allocation_count(&self) -> usize {
+        ((self.window_count + self.windows_per_allocation - 1) / self.windows_per_allocation)
+            as usize
+    }
+
+    pub fn allocation_windows(&self, allocation_idx: usize) -> i64 {
+        min(
+            self.windows_per_allocation,
+            self.window_count - allocation_idx as i64 * self.windows_per_allocation,
+        )
+    }
+
+    pub fn allocation_size(&self, allocation_idx: usize) -> i64 {
+        self.allocation_windows(allocation_idx) * self.window_size
+    }
+
+    pub fn allocation_base(&self, allocation_idx: usize) -> i64 {
+        allocation_idx as i64 * self.windows_per_allocation * self.window_size
+    }
+
+    /// Allocation index and offset inside it for the virtual test offset
+    pub fn locate(&self, test_offset: i64) -> (usize, i64) {
+        let allocation_idx =
+            (test_offset / self.window_size / self.windows_per_allocation) as usize;
+        (
+            allocation_idx,
+            test_offset - self.allocation_base(allocation_idx),
+        )
+    }
+}
+
+/// Bisection search of the largest usable test size. The requested size is tried first, after
+/// a failure the search goes down and up between the largest successful and the smallest failed
+/// size until they are closer than the precision.
+pub struct SizeSearch {
+    next: i64,
+    known_good: Option<i64>,
+    known_bad: Option<i64>,
+    min_size: i64,
+    precision: i64,
+    pub attempts: u32,
+}
+
+impl SizeSearch {
+    pub fn new(requested_size: i64, min_size: i64, precision: i64) -> Self {
+        Self {
+            next: requested_size,
+            known_good: None,
+            known_bad: None,
+            min_size,
+            precision: max(precision, 1),
+            attempts: 0,
+        }
+    }
+
+    /// Size for the current attempt
+    pub fn size(&self) -> i64 {
+        self.next
+    }
+
+    /// Whether success of the current attempt finishes the search
+    pub fn is_final(&self) -> bool {
+        self.known_bad
+            .map_or(true, |bad| bad - self.next <= self.precision)
+    }
+
+    /// Records failure of the current attempt. Returns the next size to try or None if even the
+    /// minimum size fails
+    pub fn failed(&mut self) -> Option<i64> {
+        self.attempts += 1;
+        self.known_bad = Some(self.next);
+        if self.known_good.map_or(false, |good| good >= self.next) {
+            //previously successful size failed now, so don't trust it anymore
+            self.known_good = None;
+        }
+        if self.next <= self.min_size {
+            return None;
+        }
+        let lower = self.known_good.unwrap_or(self.min_size);
+        self.next = self.bisect(lower, self.next);
+        Some(self.next)
+    }
+
+    /// Records success of the current attempt. Returns None if the search is finished and the
+    /// current size should be used, otherwise the next bigger size to try
+    pub fn succeeded(&mut self) -> Option<i64> {
+        self.attempts += 1;
+        if self.is_final() {
+            return None;
+        }
+        self.known_good = Some(self.next);
+        self.next = self.bisect(self.next, self.known_bad.unwrap_or(self.next));
+        Some(self.next)
+    }
+
+    fn bisect(&self, lower: i64, upper: i64) -> i64 {
+        if upper - lower <= self.precision {
+            return lower;
+        }
+        lower + (upper - lower) / 2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_fill_allocations_in_order() {
+        let layout = AllocationLayout::plan(1000, 600, 250, 10).unwrap();
+        assert_eq!(
+            (layout.window_size, layout.window_count),
+            (250, 4),
+            "{layout:?}"
+        );
+        assert_eq!(layout.windows_per_allocation, 2);
+        assert_eq!(layout.allocation_count(), 2);
+        assert_eq!(layout.allocation_base(1), 500);
+        assert_eq!(layout.locate(0), (0, 0));
+        assert_eq!(layout.locate(499), (0, 499));
+        assert_eq!(layout.locate(500), (1, 0));
+        assert_eq!(layout.locate(999), (1, 499));
+    }
+
+    #[test]
+    fn last_allocation_holds_the_remaining_windows() {
+        let layout = AllocationLayout::plan(1100, 600, 250, 10).unwrap();
+        assert_eq!(
+            (layout.window_size, layout.window_count),
+            (220, 5),
+            "{layout:?}"
+        );
+        assert_eq!(layout.allocation_count(), 3);
+        let sizes: Vec<i64> = (0..3).map(|idx| layout.allocation_size(idx)).collect();
+        assert_eq!(sizes, [440, 440, 220]);
+        assert_eq!(sizes.iter().sum::<i64>(), layout.total_size());
+        assert_eq!(layout.allocation_base(2), 880);
+        assert_eq!(layout.locate(879), (1, 439));
+        assert_eq!(layout.locate(880), (2, 0));
+        assert_eq!(layout.locate(1099), (2, 219));
+    }
+
+    #[test]
+    fn windows_are_rounded_down_to_granularity() {
+        let layout = AllocationLayout::plan(1000, 1000, 333, 100).unwrap();
+        assert_eq!(
+            (layout.window_size, layout.window_count),
+            (200, 4),
+            "{layout:?}"
+        );
+        assert_eq!(layout.total_size(), 800);
+        assert_eq!(layout.allocation_count(), 1);
+    }
+
+    #[test]
+    fn at_least_two_windows_are_planned() {
+        let layout = AllocationLayout::plan(100, 1000, 1000, 10).unwrap();
+        assert_eq!(
+            (layout.window_size, layout.window_count),
+            (50, 2),
+            "{layout:?}"
+        );
+    }
+
+    #[test]
+    fn allocation_smaller_than_window_holds_one_window() {
+        let layout = AllocationLayout::plan(1000, 250, 1000, 10).unwrap();
+        assert_eq!(layout.window_size, 250);
+        assert_eq!(layout.windows_per_allocation, 1);
+        assert_eq!(layout.allocation_count(), 4);
+        assert_eq!(layout.locate(750), (3, 0));
+    }
+
+    #[test]
+    fn sizes_below_granularity_are_not_planned() {
+        assert!(AllocationLayout::plan(1000, 5, 1000, 10).is_none());
+        assert!(AllocationLayout::plan(15, 1000, 1000, 10).is_none());
+    }
+
+    /// Runs the search against memory where sizes up to `limit` succeed, returns the chosen size
+    fn search(search: &mut SizeSearch, limit: i64) -> Option<i64> {
+        loop {
+            if search.size() > limit {
+                search.failed()?;
+            } else if search.succeeded().is_none() {
+                return Some(search.size());
+            }
+        }
+    }
+
+    #[test]
+    fn requested_size_is_used_when_it_succeeds() {
+        let mut size_search = SizeSearch::new(1000, 100, 10);
+        assert!(size_search.is_final());
+        assert_eq!(search(&mut size_search, 1000), Some(1000));
+        assert_eq!(size_search.attempts, 1);
+    }
+
+    #[test]
+    fn bisection_stops_within_precision() {
+        for limit in [100, 101, 389, 550, 990, 999] {
+            let mut size_search = SizeSearch::new(1000, 100, 10);
+            let chosen = search(&mut size_search, limit).unwrap();
+            assert!(
+                chosen <= limit && limit - chosen <= 10,
+                "limit {limit} chosen {chosen}"
+            );
+            assert!(size_search.attempts <= 10, "{}", size_search.attempts);
+        }
+    }
+
+    #[test]
+    fn bisection_halves_the_range() {
+        let mut size_search = SizeSearch::new(1000, 100, 10);
+        assert_eq!(size_search.failed(), Some(550));
+        assert!(!size_search.is_final());
+        assert_eq!(size_search.succeeded(), Some(775));
+        assert_eq!(size_search.failed(), Some(662));
+    }
+
+    #[test]
+    fn known_good_size_is_retested_at_precision() {
+        let mut size_search = SizeSearch::new(1000, 100, 10);
+        size_search.failed();
+        size_search.succeeded();
+        size_search.failed();
+        size_search.succeeded();
+        //662 is good and 775 is bad, failures halve the range down to the precision
+        assert_eq!(size_search.size(), 718);
+        assert_eq!(size_search.failed(), Some(690));
+        assert_eq!(size_search.failed(), Some(676));
+        assert_eq!(size_search.failed(), Some(669));
+        //669 is within the precision from the good 662, so that one is tested again
+        assert_eq!(size_search.failed(), Some(662));
+        assert!(size_search.is_final());
+        assert_eq!(size_search.succeeded(), None);
+        assert_eq!(size_search.size(), 662);
+    }
+
+    #[test]
+    fn failed_known_good_size_restarts_from_minimum() {
+        let mut size_search = SizeSearch::new(1000, 100, 10);
+        assert_eq!(size_search.failed(), Some(550));
+        assert_eq!(size_search.succeeded(), Some(775));
+        assert_eq!(size_search.failed(), Some(662));
+        assert_eq!(size_search.failed(), Some(606));
+        assert_eq!(size_search.failed(), Some(578));
+        assert_eq!(size_search.failed(), Some(564));
+        assert_eq!(size_search.failed(), Some(557));
+        assert_eq!(size_search.failed(), Some(550));
+        //the size succeeded before, but fails now
+        assert_eq!(size_search.failed(), Some(325));
+    }
+
+    #[test]
+    fn failure_at_minimum_ends_the_search() {
+        let mut size_search = SizeSearch::new(1000, 100, 10);
+        assert_eq!(search(&mut size_search, 50), None);
+        assert_eq!(size_search.size(), 100);
+        let mut at_minimum = SizeSearch::new(100, 100, 10);
+        assert_eq!(at_minimum.failed(), None);
+        assert_eq!(at_minimum.attempts, 1);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{allocation::AllocationLayout, engine::IOBuf};
+use byte_strings::c_str;
+use std::ffi::CStr;
+
+/// Entry points of the test shader, each one processes a whole test window
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Kernel {
+    Read,
+    Write,
+    March,
+}
+
+impl Kernel {
+    pub const ALL: [Kernel; 3] = [Kernel::Read, Kernel::Write, Kernel::March];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kernel::Read => "read",
+            Kernel::Write => "write",
+            Kernel::March => "march",
+        }
+    }
+
+    pub fn entry_point(self) -> &'static CStr {
+        match self {
+            Kernel::Read => c_str!("read"),
+            Kernel::Write => c_str!("write"),
+            Kernel::March => c_str!("march"),
+        }
+    }
+}
+
+/// Failure of adding a test allocation
+pub(crate) enum AllocationError {
+    /// device memory of this size can't be allocated, smaller allocations may still succeed
+    Memory(Box<dyn std::error::Error>),
+    Other(Box<dyn std::error::Error>),
+}
+
+/// Device operations used by the testing: test memory allocations, the buffer shared with the
+/// kernels and the kernel dispatches. Dispatch errors are [`crate::GpuHang`] and
+/// [`crate::DeviceLost`] for the failures of the device itself.
+pub(crate) trait Backend {
+    /// Biggest size of a single allocation
+    fn max_allocation_size(&self) -> i64;
+    /// Memory type index of the test allocations
+    fn memory_type(&self) -> u32;
+    /// Memory type and heap of the test allocations for the verbose output
+    fn describe_memory(&self) -> String;
+    /// Adds a test allocation after the existing ones
+    fn allocate(&self, size: i64) -> Result<(), AllocationError>;
+    fn free_allocations(&self);
+    fn read_io(&self) -> IOBuf;
+    fn write_io(&self, io: &IOBuf);
+    /// Changes part of the shared buffer without copying the whole one
+    fn update_io(&self, update: &mut dyn FnMut(&mut IOBuf));
+    /// Runs the kernel for the window at the test offset and waits for its completion
+    fn dispatch(
+        &self,
+        kernel: Kernel,
+        layout: &AllocationLayout,
+        test_offset: i64,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+    fn wait_idle(&self) -> Result<(), Box<dyn std::error::Error>>;
+    /// Testing iteration of the following calls, the injected call faults depend on it
+    fn set_iteration(&self, _iteration: u32) {}
+}
@@ -0,0 +1,656 @@
+use memtest_vulkan::inject::{FaultModel, FaultPath, FaultSpec, LANES};
+use memtest_vulkan::modes::TestMode;
+use memtest_vulkan::pattern::TestPattern;
+use memtest_vulkan::schedule::{LoadPhase, PhaseKind};
+use memtest_vulkan::TestConfig;
+use std::{ffi::OsString, fmt, time::Duration};
+
+pub const USAGE: &str = "\
+Usage: memtest_vulkan [OPTIONS]
+
+Options:
+  -d, --device <INDEX>     test device with given index from the device list, skips selection prompt
+  -a, --all-devices        test all devices in parallel, each by its own process
+  -m, --memory <SIZE>      memory size to test, like 3.5GB, 2048MiB or 1000000000 (bytes)
+                           GB/GiB, MB/MiB, KB/KiB suffixes are all 1024-based; default is all available
+      --alloc-precision <SIZE>
+                           precision of the search for the largest usable memory size when the
+                           wanted size can't be allocated, default is 256MB
+  -t, --duration <TIME>    stop testing after given time, like 90s, 10m, 2h or 1h30m
+  -i, --iterations <N>     stop testing after given count of full write+check iterations
+  -s, --standard           stop testing when the standard 5-minute test is done
+      --mode <MODE>        testing algorithm: standard (default) - write once and re-read a part of
+                           memory, march-c - March C- read-verify-write element sequence,
+                           retention - fill memory, keep gpu idle for a hold time, verify
+      --hold <TIME>        idle hold time of the retention mode, default is 60s
+      --hang-timeout <TIME>
+                           report GPU hang if a single dispatch isn't finished in this time,
+                           default is 30s
+      --confirm-reads <N>  re-read a window with errors N times without rewriting, then rewrite and
+                           re-read it to tell transient, sticky and write-path errors apart;
+                           default is 3, 0 disables the confirmation
+      --device-lost-retries <N>
+                           respawn the test process up to N times after the device is lost,
+                           counting each loss; default is 0 - stop testing on the first loss
+      --schedule <LIST>    cycle gpu load by comma-separated phases of standard mode: load:TIME,
+                           idle:TIME and check - single read dispatch right after previous phase,
+                           like load:5s,idle:3s,check,load:200ms,check
+  -p, --pattern <LIST>     comma-separated data patterns used in turn for each iteration or 'all':
+                           address (default), zeros, ones, checkerboard, walking-ones,
+                           walking-zeros, inverted-address
+  -v, --verbose            print diagnostic information, repeat (-vv) for even more details
+      --verbosity <LEVEL>  set verbosity level explicitly: 0 - normal, 1 - verbose, 2 - debug
+      --log <PATH>         log file path, default is memtest_vulkan.log in the current directory
+      --no-log             don't write log file
+      --events <PATH>      append machine-readable JSON Lines events about test progress to a file
+      --heat-map <PATH>    save the error heat map over the tested memory as a PPM image at the end
+      --inject <FAULT>     inject a data fault to check the error reports: flip:ADDRESS:BIT,
+                           window-flip:OFFSET:BIT (at the offset of every test window),
+                           stuck-at-0:LANE, stuck-at-1:LANE (lane 0..127 as in the lane tables),
+                           alias:MASK (accesses land at address XOR mask) or storm:RATE (random
+                           flip per value with given probability); optional /write (default),
+                           /read or /reread path and @ITERATION, like flip:0x1000ADBA4:22/read@2.
+                           Also taken from MEMTEST_VULKAN_INJECT environment variable
+  -n, --non-interactive    no device prompt and no keypress waiting; exit code reports test status
+  -h, --help               print this help
+  -V, --version            print version
+";
+
+#[derive(Debug)]
+pub struct ArgsError(String);
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+pub enum Command {
+    Run(Box<Args>),
+    Help,
+    Version,
+}
+
+pub struct Args {
+    pub worker: bool,
+    pub probe: bool,
+    pub ipc: bool,
+    pub device_label: Option<usize>,
+    pub all_devices: bool,
+    pub max_test_bytes: Option<i64>,
+    pub alloc_precision: i64,
+    pub duration: Option<Duration>,
+    pub iterations: Option<i32>,
+    pub standard_only: bool,
+    pub patterns: Vec<TestPattern>,
+    pub mode: TestMode,
+    pub hold_duration: Duration,
+    pub hang_timeout: Duration,
+    pub confirm_reads: u32,
+    pub device_lost_retries: u32,
+    pub schedule: Vec<LoadPhase>,
+    pub verbosity: Option<u8>,
+    pub log_file: Option<String>,
+    pub events_file: Option<String>,
+    pub heat_map_file: Option<String>,
+    pub inject: Option<FaultSpec>,
+    pub interactive: bool,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        let test = TestConfig::default();
+        Self {
+            worker: false,
+            probe: false,
+            ipc: false,
+            device_label: None,
+            all_devices: false,
+            max_test_bytes: None,
+            alloc_precision: test.alloc_precision,
+            duration: None,
+            iterations: None,
+            standard_only: false,
+            patterns: test.patterns,
+            mode: test.mode,
+            hold_duration: test.hold_duration,
+            hang_timeout: test.hang_timeout,
+            confirm_reads: test.confirm_reads,
+            device_lost_retries: 0,
+            schedule: Vec::new(),
+            verbosity: None,
+            log_file: Some(DEFAULT_LOG_FILE.into()),
+            events_file: None,
+            heat_map_file: None,
+            inject: None,
+            interactive: true,
+        }
+    }
+}
+
+pub const DEFAULT_LOG_FILE: &str = "memtest_vulkan.log";
+
+fn err<T>(msg: String) -> Result<T, ArgsError> {
+    Err(ArgsError(msg))
+}
+
+/// Parses arguments following argv0.
+pub fn parse_args(args: impl IntoIterator<Item = OsString>) -> Result<Command, ArgsError> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+    while let Some(os_arg) = args.next() {
+        let arg = match os_arg.to_str() {
+            Some(arg) => arg.to_string(),
+            None => return err(format!("argument {os_arg:?} is not valid unicode")),
+        };
+        //support both "--option value" and "--option=value" forms
+        let (name, mut inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => {
+                (name.to_string(), Some(value.to_string()))
+            }
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| -> Result<String, ArgsError> {
+            if let Some(value) = inline_value.take() {
+                return Ok(value);
+            }
+            match args.next().map(|v| v.into_string()) {
+                Some(Ok(value)) => Ok(value),
+                Some(Err(value)) => err(format!("value {value:?} for {name} is not valid unicode")),
+                None => err(format!("option {name} requires a value")),
+            }
+        };
+        match name.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "--worker" => parsed.worker = true,
+            "--probe" => parsed.probe = true,
+            "--ipc" => parsed.ipc = true,
+            "-d" | "--device" => {
+                let index = value(&name)?;
+                parsed.device_label = Some(index.parse().or_else(|_| {
+                    err(format!(
+                        "invalid device index '{index}', expected a number from the device list"
+                    ))
+                })?)
+            }
+            "-a" | "--all-devices" => parsed.all_devices = true,
+            "-m" | "--memory" => parsed.max_test_bytes = Some(parse_size(&value(&name)?)?),
+            "--alloc-precision" => parsed.alloc_precision = parse_size(&value(&name)?)?,
+            "-t" | "--duration" => parsed.duration = Some(parse_duration(&value(&name)?)?),
+            "-i" | "--iterations" => {
+                let count = value(&name)?;
+                parsed.iterations = Some(match count.parse::<i32>() {
+                    Ok(count) if count > 0 => count,
+                    _ => {
+                        return err(format!(
+                            "invalid iteration count '{count}', expected positive number"
+                        ))
+                    }
+                })
+            }
+            "-s" | "--standard" => parsed.standard_only = true,
+            "--mode" => {
+                let mode = value(&name)?;
+                parsed.mode = match TestMode::from_name(&mode) {
+                    Some(mode) => mode,
+                    None => {
+                        return err(format!(
+                            "unknown mode '{mode}', expected standard, march-c or retention"
+                        ))
+                    }
+                }
+            }
+            "--hold" => parsed.hold_duration = parse_duration(&value(&name)?)?,
+            "--hang-timeout" => parsed.hang_timeout = parse_duration(&value(&name)?)?,
+            "--confirm-reads" => {
+                let count = value(&name)?;
+                parsed.confirm_reads = match count.parse::<u32>() {
+                    Ok(count) => count,
+                    _ => {
+                        return err(format!(
+                            "invalid re-read count '{count}', expected non-negative number"
+                        ))
+                    }
+                }
+            }
+            "--device-lost-retries" => {
+                let count = value(&name)?;
+                parsed.device_lost_retries = match count.parse::<u32>() {
+                    Ok(count) => count,
+                    _ => {
+                        return err(format!(
+                            "invalid retry count '{count}', expected non-negative number"
+                        ))
+                    }
+                }
+            }
+            "--schedule" => parsed.schedule = parse_schedule(&value(&name)?)?,
+            "-p" | "--pattern" => parsed.patterns = parse_patterns(&value(&name)?)?,
+            "-v" | "--verbose" => parsed.verbosity = Some(parsed.verbosity.unwrap_or(0) + 1),
+            "-vv" => parsed.verbosity = Some(parsed.verbosity.unwrap_or(0) + 2),
+            "--verbosity" => {
+                let level = value(&name)?;
+                parsed.verbosity = Some(match level.parse::<u8>() {
+                    Ok(level) if level <= 2 => level,
+                    _ => {
+                        return err(format!(
+                            "invalid verbosity level '{level}', expected 0, 1 or 2"
+                        ))
+                    }
+                })
+            }
+            "--log" => {
+                let path = value(&name)?;
+                if path.is_empty() {
+                    return err("log path can't be empty, use --no-log to disable logging".into());
+                }
+                parsed.log_file = Some(path)
+            }
+            "--no-log" => parsed.log_file = None,
+            "--events" => {
+                let path = value(&name)?;
+                if path.is_empty() {
+                    return err("events path can't be empty".into());
+                }
+                parsed.events_file = Some(path)
+            }
+            "--heat-map" => {
+                let path = value(&name)?;
+                if path.is_empty() {
+                    return err("heat map path can't be empty".into());
+                }
+                parsed.heat_map_file = Some(path)
+            }
+            "--inject" => parsed.inject = Some(parse_fault(&value(&name)?)?),
+            "-n" | "--non-interactive" => parsed.interactive = false,
+            _ if name.starts_with('-') => return err(format!("unknown option '{name}'")),
+            _ => return err(format!("unexpected argument '{arg}'")),
+        }
+        if let Some(value) = inline_value {
+            return err(format!(
+                "option {name} doesn't take a value, but '{value}' given"
+            ));
+        }
+    }
+    if !parsed.schedule.is_empty() && parsed.mode != TestMode::Standard {
+        return err(format!(
+            "--schedule is supported only by standard mode, not by {}",
+            parsed.mode
+        ));
+    }
+    if parsed.all_devices && parsed.device_label.is_some() {
+        return err("--all-devices can't be combined with --device".into());
+    }
+    if parsed.all_devices && parsed.worker {
+        //workers of --all-devices are started for a single device each
+        return err("--all-devices can't be used by --worker".into());
+    }
+    if (parsed.probe || parsed.ipc) && !parsed.worker {
+        return err("--probe and --ipc are options of --worker".into());
+    }
+    if parsed.worker {
+        //worker is always started by the console process with explicit device and size
+        parsed.interactive = false;
+        if parsed.device_label.is_none() || parsed.max_test_bytes.is_none() {
+            return err("--worker requires both --device and --memory".into());
+        }
+    }
+    Ok(Command::Run(Box::new(parsed)))
+}
+
+/// Parses comma-separated list of pattern names, `all` means every known pattern.
+pub fn parse_patterns(text: &str) -> Result<Vec<TestPattern>, ArgsError> {
+    if text.trim() == "all" {
+        return Ok(TestPattern::ALL.to_vec());
+    }
+    let mut patterns = Vec::new();
+    for name in text.split(',').map(str::trim) {
+        match TestPattern::from_name(name) {
+            Some(pattern) => patterns.push(pattern),
+            None => {
+                return err(format!(
+                    "unknown pattern '{name}', expected 'all' or some of: {}",
+                    TestPattern::names_list()
+                ))
+            }
+        }
+    }
+    Ok(patterns)
+}
+
+/// Parses comma-separated list of load schedule phases like `load:5s,idle:3s,check`.
+pub fn parse_schedule(text: &str) -> Result<Vec<LoadPhase>, ArgsError> {
+    let mut phases = Vec::new();
+    for phase in text.split(',').map(str::trim) {
+        let (name, duration) = match phase.split_once(':') {
+            Some((name, duration)) => (name, Some(parse_duration(duration)?)),
+            None => (phase, None),
+        };
+        phases.push(match (name, duration) {
+            ("load", Some(duration)) => LoadPhase {
+                kind: PhaseKind::Load,
+                duration,
+            },
+            ("idle", Some(duration)) => LoadPhase {
+                kind: PhaseKind::Idle,
+                duration,
+            },
+            ("check", None) => LoadPhase {
+                kind: PhaseKind::Check,
+                duration: Duration::ZERO,
+            },
+            _ => {
+                return err(format!(
+                    "invalid schedule phase '{phase}', expected load:TIME, idle:TIME or check"
+                ))
+            }
+        });
+    }
+    if !phases.iter().any(|phase| phase.kind == PhaseKind::Load) {
+        return err(format!("schedule '{text}' has no load phase"));
+    }
+    Ok(phases)
+}
+
+/// Parses injected fault like `flip:0x1000ADBA4:22/read@2`: the fault model with its parameters,
+/// then optional path and iteration.
+pub fn parse_fault(text: &str) -> Result<FaultSpec, ArgsError> {
+    let invalid = |reason: &str| ArgsError(format!("invalid fault '{text}': {reason}"));
+    let (rest, iteration) = match text.trim().rsplit_once('@') {
+        Some((rest, iteration)) => match iteration.parse::<u32>() {
+            Ok(iteration) if iteration > 0 => (rest, Some(iteration)),
+            _ => return Err(invalid("iteration must be a positive number")),
+        },
+        None => (text.trim(), None),
+    };
+    let (model, path) = match rest.split_once('/') {
+        Some((model, path)) => match FaultPath::from_name(path) {
+            Some(path) => (model, path),
+            None => return Err(invalid("path must be write, read or reread")),
+        },
+        None => (rest, FaultPath::Write),
+    };
+    let number = |text: &str| match text.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => text.parse::<i64>().ok(),
+    };
+    let below = |text: &str, limit: u32| text.parse::<u32>().ok().filter(|value| *value < limit);
+    let model = match model.split(':').collect::<Vec<_>>().as_slice() {
+        ["flip", address, bit] => match (number(address), below(bit, 32)) {
+            (Some(address), Some(bit)) if address >= 0 => FaultModel::BitFlip { address, bit },
+            _ => return Err(invalid("expected byte address and bit 0..31")),
+        },
+        ["window-flip", offset, bit] => match (number(offset), below(bit, 32)) {
+            (Some(offset), Some(bit)) if offset >= 0 => FaultModel::WindowFlip { offset, bit },
+            _ => return Err(invalid("expected byte offset and bit 0..31")),
+        },
+        [stuck @ ("stuck-at-0" | "stuck-at-1"), lane] => match below(lane, LANES) {
+            Some(lane) => FaultModel::StuckLane {
+                lane,
+                value: *stuck == "stuck-at-1",
+            },
+            None => return Err(invalid("lane must be 0..127")),
+        },
+        ["alias", mask] => match number(mask) {
+            //the shader gets the mask in vectors as u32
+            Some(mask) if mask > 0 && mask % 16 == 0 && mask / 16 <= u32::MAX as i64 => {
+                FaultModel::Alias { mask }
+            }
+            _ => {
+                return Err(invalid(
+                    "mask must be a positive multiple of 16 bytes below 64GiB",
+                ))
+            }
+        },
+        ["storm", rate] => match rate.parse::<f64>() {
+            Ok(rate) if rate > 0.0 && rate <= 1.0 => FaultModel::Storm { rate },
+            _ => return Err(invalid("rate must be a probability in 0..1 range")),
+        },
+        _ => return Err(invalid(
+            "expected flip:ADDRESS:BIT, window-flip:OFFSET:BIT, stuck-at-0:LANE, stuck-at-1:LANE, alias:MASK or storm:RATE",
+        )),
+    };
+    Ok(FaultSpec {
+        model,
+        path,
+        iteration,
+    })
+}
+
+/// Parses size like `3.5GB`, `2048MiB` or plain byte count. All suffixes are 1024-based,
+/// matching the GB values printed by the tool.
+pub fn parse_size(text: &str) -> Result<i64, ArgsError> {
+    let trimmed = text.trim();
+    let number_end = trimmed
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(trimmed.len());
+    let (number, suffix) = trimmed.split_at(number_end);
+    let multiplier: i64 = match suffix.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        "t" | "tb" | "tib" => 1024 * 1024 * 1024 * 1024,
+        _ => {
+            return err(format!(
+                "invalid size suffix in '{text}', expected one of B, KB, MB, GB, TB"
+            ))
+        }
+    };
+    let bytes = match number.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => value * multiplier as f64,
+        _ => {
+            return err(format!(
+                "invalid size '{text}', expected positive number like 3.5GB"
+            ))
+        }
+    };
+    if bytes >= i64::MAX as f64 {
+        return err(format!("size '{text}' is too large"));
+    }
+    Ok(bytes as i64)
+}
+
+/// Parses duration as a sequence of number+unit pairs like `90s`, `10m`, `1h30m` or `500ms`.
+/// Number without unit means seconds.
+pub fn parse_duration(text: &str) -> Result<Duration, ArgsError> {
+    let invalid = || {
+        err(format!(
+            "invalid duration '{text}', expected value like 90s, 10m or 1h30m"
+        ))
+    };
+    let mut rest = text.trim();
+    if rest.is_empty() {
+        return invalid();
+    }
+    let mut total = Duration::ZERO;
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let unit_end = rest[number_end..]
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .map_or(rest.len(), |pos| number_end + pos);
+        let value = match rest[..number_end].parse::<f64>() {
+            Ok(value) if value.is_finite() && value >= 0.0 => value,
+            _ => return invalid(),
+        };
+        let unit_secs = match &rest[number_end..unit_end] {
+            "" | "s" | "sec" => 1.0,
+            "ms" => 0.001,
+            "m" | "min" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            _ => return invalid(),
+        };
+        let secs = value * unit_secs;
+        //from_secs_f64 panics on values not fitting the duration
+        if secs >= u64::MAX as f64 {
+            return invalid();
+        }
+        total = match total.checked_add(Duration::from_secs_f64(secs)) {
+            Some(total) => total,
+            None => return invalid(),
+        };
+        rest = &rest[unit_end..];
+    }
+    if total.is_zero() {
+        return err(format!("duration '{text}' must be greater than zero"));
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: i64 = 1024 * 1024 * 1024;
+
+    fn error_of<T: fmt::Debug>(result: Result<T, ArgsError>) -> String {
+        result.unwrap_err().0
+    }
+
+    #[test]
+    fn sizes_with_suffixes() {
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("2k").unwrap(), 2048);
+        assert_eq!(parse_size("2048MiB").unwrap(), 2 * GB);
+        assert_eq!(parse_size(" 3.5GB ").unwrap(), 7 * GB / 2);
+        assert_eq!(parse_size("1 gb").unwrap(), GB);
+        assert_eq!(parse_size("2TB").unwrap(), 2048 * GB);
+    }
+
+    #[test]
+    fn bad_sizes() {
+        assert!(error_of(parse_size("")).contains("expected positive number"));
+        assert!(error_of(parse_size("0")).contains("expected positive number"));
+        assert!(error_of(parse_size("0.0GB")).contains("expected positive number"));
+        assert!(error_of(parse_size("-1GB")).contains("invalid size suffix"));
+        assert!(error_of(parse_size("1.2.3GB")).contains("expected positive number"));
+        assert!(error_of(parse_size("5XB")).contains("invalid size suffix"));
+        assert!(error_of(parse_size("10000000TB")).contains("too large"));
+    }
+
+    #[test]
+    fn durations_with_units() {
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("10min").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("1d").unwrap(), Duration::from_secs(86400));
+    }
+
+    #[test]
+    fn multi_unit_durations() {
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("2m30s").unwrap(), Duration::from_secs(150));
+        assert_eq!(
+            parse_duration("1s500ms").unwrap(),
+            Duration::from_millis(1500)
+        );
+        //zero parts are fine as long as the total isn't zero
+        assert_eq!(parse_duration("0h5m").unwrap(), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn bad_durations() {
+        assert!(error_of(parse_duration("")).contains("invalid duration"));
+        assert!(error_of(parse_duration("10x")).contains("invalid duration"));
+        assert!(error_of(parse_duration("h")).contains("invalid duration"));
+        assert!(error_of(parse_duration("1h 30m")).contains("invalid duration"));
+        assert!(error_of(parse_duration("0s")).contains("greater than zero"));
+        assert!(error_of(parse_duration("0h0m")).contains("greater than zero"));
+    }
+
+    #[test]
+    fn huge_durations_are_rejected_without_panic() {
+        assert!(parse_duration("10000000000000000000").is_ok());
+        assert!(error_of(parse_duration("100000000000000000000")).contains("invalid duration"));
+        assert!(error_of(parse_duration("1000000000000000000d")).contains("invalid duration"));
+        //each part fits, their sum doesn't
+        let max_secs = u64::MAX / 2 + 1;
+        assert!(error_of(parse_duration(&format!("{max_secs}s{max_secs}s")))
+            .contains("invalid duration"));
+    }
+
+    #[test]
+    fn pattern_lists() {
+        assert_eq!(parse_patterns("all").unwrap(), TestPattern::ALL.to_vec());
+        assert_eq!(
+            parse_patterns("zeros, walking-ones").unwrap(),
+            [TestPattern::Zeros, TestPattern::WalkingOnes]
+        );
+        assert!(error_of(parse_patterns("zeros,twos")).contains("unknown pattern 'twos'"));
+        assert!(error_of(parse_patterns("")).contains("unknown pattern ''"));
+        assert!(error_of(parse_patterns("zeros,")).contains("unknown pattern ''"));
+    }
+
+    #[test]
+    fn schedules() {
+        assert_eq!(
+            parse_schedule("load:5s,idle:1m,check").unwrap(),
+            [
+                LoadPhase {
+                    kind: PhaseKind::Load,
+                    duration: Duration::from_secs(5),
+                },
+                LoadPhase {
+                    kind: PhaseKind::Idle,
+                    duration: Duration::from_secs(60),
+                },
+                LoadPhase {
+                    kind: PhaseKind::Check,
+                    duration: Duration::ZERO,
+                },
+            ]
+        );
+        assert!(error_of(parse_schedule("idle:5s,check")).contains("has no load phase"));
+        assert!(error_of(parse_schedule("load")).contains("invalid schedule phase 'load'"));
+        assert!(error_of(parse_schedule("load:5s,check:1s")).contains("invalid schedule phase"));
+        assert!(error_of(parse_schedule("load:0s")).contains("greater than zero"));
+        assert!(error_of(parse_schedule("")).contains("invalid schedule phase ''"));
+    }
+
+    #[test]
+    fn faults_round_trip_through_display() {
+        for text in [
+            "flip:0x1000ADBA4:22/write",
+            "window-flip:0xADBA4:22/write@3",
+            "stuck-at-0:37/read",
+            "stuck-at-1:127/reread@2",
+            "alias:0x100000/write",
+            "storm:0.0001/read@3",
+        ] {
+            assert_eq!(parse_fault(text).unwrap().to_string(), text);
+        }
+        let fault = parse_fault("flip:4096:5").unwrap();
+        assert_eq!(
+            fault.model,
+            FaultModel::BitFlip {
+                address: 4096,
+                bit: 5
+            }
+        );
+        assert_eq!(fault.path, FaultPath::Write);
+        assert_eq!(fault.iteration, None);
+    }
+
+    #[test]
+    fn bad_faults() {
+        assert!(error_of(parse_fault("flip:0x10:32")).contains("bit 0..31"));
+        assert!(error_of(parse_fault("flip:-16:1")).contains("bit 0..31"));
+        assert!(error_of(parse_fault("stuck-at-1:128")).contains("lane must be 0..127"));
+        assert!(error_of(parse_fault("alias:0x18")).contains("multiple of 16"));
+        assert!(error_of(parse_fault("alias:0x1000000000")).contains("below 64GiB"));
+        assert!(parse_fault("alias:0xFFFFFFFF0").is_ok());
+        assert!(error_of(parse_fault("storm:0")).contains("probability"));
+        assert!(error_of(parse_fault("storm:1.5")).contains("probability"));
+        assert!(error_of(parse_fault("flip:0x10:1/sideways")).contains("path must be"));
+        assert!(error_of(parse_fault("flip:0x10:1@0")).contains("iteration must be"));
+        assert!(error_of(parse_fault("")).contains("expected flip:ADDRESS:BIT"));
+    }
+}
@@ -1,6 +1,7 @@
 use std::sync::atomic::{AtomicBool, AtomicU8, Ordering::SeqCst};
 
 static INTERRUPT_REQUESTED: AtomicBool = AtomicBool::new(false);
+static HEAT_MAP_REQUESTED: AtomicBool = AtomicBool::new(false);
 pub mod app_status {
     pub const SIGNATURE: u8 = 0b01000000u8;
     pub const SIGNATURE_MASK: u8 = 0b11100000u8;
@@ -32,6 +33,31 @@ pub fn close_requested() -> bool {
     INTERRUPT_REQUESTED.swap(false, SeqCst)
 }
 
+/// Same as [`close_requested`], but doesn't consume the resettable interrupt request
+pub fn close_pending() -> bool {
+    check_any_bits_set(fetch_status(), app_status::QUIT_JOB_REQUESTED)
+        || INTERRUPT_REQUESTED.load(SeqCst)
+}
+
+/// Consumes the on-demand request to print the error heat map, sent by SIGUSR1 on unix
+pub fn heat_map_requested() -> bool {
+    HEAT_MAP_REQUESTED.swap(false, SeqCst)
+}
+
+/// Requests the running test to stop, same as the interrupt handler does
+pub fn request_interrupt() {
+    INTERRUPT_REQUESTED.swap(true, SeqCst);
+}
+
+/// Clears the status and requests left by the previous test run in this process
+pub fn reset_status() {
+    APP_STATUS.fetch_and(
+        app_status::SIGNATURE | app_status::USE_GRACEFUL_HANDLER,
+        SeqCst,
+    );
+    INTERRUPT_REQUESTED.swap(false, SeqCst);
+}
+
 pub fn declare_exit_due_timeout() {
     raise_status_bit(app_status::QUIT_JOB_REQUESTED);
     INTERRUPT_REQUESTED.swap(true, SeqCst);
@@ -86,6 +112,10 @@ fn immediate_exit_with_status() -> ! {
     unsafe { windows_sys::Win32::System::Threading::ExitProcess(APP_STATUS.load(SeqCst) as u32) }
 }
 
+/// Passes the heat map request received by this process to the worker process
+#[cfg(windows)]
+pub fn forward_heat_map_request(_pid: u32) {}
+
 #[cfg(unix)]
 pub fn setup_handler_impl() {
     use nix::sys::signal;
@@ -108,6 +138,25 @@ pub fn setup_handler_impl() {
         let _ = signal::sigaction(signal::SIGHUP, &sig_action);
         let _ = signal::sigaction(signal::SIGQUIT, &sig_action);
     }
+    extern "C" fn heat_map_handler(_: nix::libc::c_int) {
+        HEAT_MAP_REQUESTED.store(true, SeqCst);
+    }
+    unsafe {
+        let heat_map_action = signal::SigAction::new(
+            signal::SigHandler::Handler(heat_map_handler),
+            signal::SaFlags::SA_RESTART,
+            signal::SigSet::empty(),
+        );
+        let _ = signal::sigaction(signal::SIGUSR1, &heat_map_action);
+    }
+}
+
+/// Passes the heat map request received by this process to the worker process
+#[cfg(unix)]
+pub fn forward_heat_map_request(pid: u32) {
+    if heat_map_requested() {
+        unsafe { nix::libc::kill(pid as nix::libc::pid_t, nix::libc::SIGUSR1) };
+    }
 }
 
 #[cfg(unix)]
@@ -0,0 +1,339 @@
+use std::fmt;
+
+const BITS: usize = 32;
+const COMPONENTS: [&str; 4] = ["x", "y", "z", "w"];
+/// Findings less likely than this are not reported
+const MIN_CONFIDENCE: f64 = 0.1;
+
+/// Bit-level statistics of a single error block, as counted by the shader for each wrong value.
+/// Single-bit errors are counted by lanes: bit indices of all 4 vector components. Toggle count
+/// index is the number of flipped bits, except index 0 counting values with all 32 bits flipped;
+/// values with all bits set are counted separately from the ones in value table.
+pub struct ErrorStats<'a> {
+    pub single_bit_lanes: &'a [u32; BITS * 4],
+    pub toggle_count: &'a [u32; BITS],
+    pub ones_in_value: &'a [u32; BITS],
+    pub all_ones_count: u32,
+    pub tested_values: i64,
+}
+
+/// Possible cause of the errors with estimated confidence in 0..=1 range
+pub struct Finding {
+    pub confidence: f64,
+    pub description: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:3.0}% {}", self.confidence * 100.0, self.description)
+    }
+}
+
+fn sum(values: &[u32]) -> f64 {
+    values.iter().map(|value| *value as f64).sum()
+}
+
+/// Flipped bits of the toggle count table index
+fn toggled_bits(index: usize) -> usize {
+    if index == 0 {
+        BITS
+    } else {
+        index
+    }
+}
+
+/// Findings ordered from the most likely one, same rules as described in the Readme section
+/// about the error kinds
+pub fn diagnose(stats: &ErrorStats) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let errors = sum(stats.toggle_count);
+    if errors == 0.0 {
+        return findings;
+    }
+    let mut add = |confidence: f64, description: String| {
+        findings.push(Finding {
+            confidence: confidence.clamp(0.0, 1.0),
+            description,
+        })
+    };
+
+    //each wrong value is counted once in every table, so mismatch means the counters are broken
+    let values = sum(stats.ones_in_value) + stats.all_ones_count as f64;
+    let single_bits = sum(stats.single_bit_lanes);
+    let toggle_1 = stats.toggle_count[1] as f64;
+    if values > stats.tested_values as f64 {
+        add(
+            0.95,
+            "corruption of the error counters area: more errors than tested values".into(),
+        );
+    } else {
+        let mismatch = f64::max(
+            (values - errors).abs() / f64::max(values, errors),
+            (single_bits - toggle_1).abs() / f64::max(single_bits, toggle_1).max(1.0),
+        );
+        if mismatch > 0.0 {
+            add(
+                0.5 + mismatch / 2.0,
+                "corruption of the error counters area: inconsistent stats tables".into(),
+            );
+        }
+    }
+
+    let single_share = toggle_1 / errors;
+    if single_bits > 0.0 {
+        let (worst_lane, worst_count) = stats
+            .single_bit_lanes
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, count)| **count)
+            .unwrap_or((0, &0));
+        let worst_share = *worst_count as f64 / single_bits;
+        add(
+            single_share * worst_share,
+            format!(
+                "likely single data line fault on bit {} of {} component (lane {worst_lane})",
+                worst_lane % BITS,
+                COMPONENTS[worst_lane / BITS]
+            ),
+        );
+        let flipped_bits = stats
+            .single_bit_lanes
+            .iter()
+            .filter(|count| **count > 0)
+            .count();
+        if flipped_bits > 1 {
+            add(
+                single_share * (1.0 - worst_share),
+                format!(
+                    "single-bit flips spread over {flipped_bits} lanes, likely marginal memory timings"
+                ),
+            );
+        }
+    }
+
+    let share = |bits: std::ops::RangeInclusive<usize>| {
+        sum(&stats.toggle_count[*bits.start()..=*bits.end()]) / errors
+    };
+    let dbi_share = share(7..=8);
+    let dbi_neighbours = share(6..=6) + share(9..=9);
+    if dbi_share > 0.0 {
+        add(
+            dbi_share * dbi_share / (dbi_share + dbi_neighbours),
+            "likely data bus inversion (DBI) line errors: whole bytes inverted".into(),
+        );
+    }
+    add(
+        share(2..=6) * (1.0 - share(7..=8)),
+        "multi-bit transmission errors".into(),
+    );
+
+    //random data from wrong cells flips half of the bits in binomial distribution
+    let mean_toggles = stats
+        .toggle_count
+        .iter()
+        .enumerate()
+        .map(|(index, count)| toggled_bits(index) as f64 * *count as f64)
+        .sum::<f64>()
+        / errors;
+    let closeness = 1.0 - (mean_toggles - BITS as f64 / 2.0).abs() / (BITS as f64 / 2.0);
+    add(
+        share(10..=22) * closeness,
+        "address-bus-like garbage: data read from wrong cells".into(),
+    );
+
+    if values > 0.0 {
+        add(
+            (stats.ones_in_value[0] as f64 + stats.all_ones_count as f64) / values,
+            "values stuck at 0x00000000 or 0xFFFFFFFF, likely EDC or memory controller failure"
+                .into(),
+        );
+    }
+
+    findings.retain(|finding| finding.confidence >= MIN_CONFIDENCE);
+    findings.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    findings
+}
+
+/// Share of errors with the address bit set compared to the share expected when errors are
+/// spread evenly over the tested values
+pub struct AddressBitBias {
+    /// bit of the byte offset in the whole tested memory
+    pub bit: usize,
+    pub ones_share: f64,
+    pub expected_share: f64,
+}
+
+impl fmt::Display for AddressBitBias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "1 in {:.0}% of errors, expected {:.0}%",
+            self.ones_share * 100.0,
+            self.expected_share * 100.0
+        )
+    }
+}
+
+/// Per-address-bit statistics of the failing values. An address line fault shows up as errors
+/// sharing the value of the faulty bit.
+pub struct AddressBitStats {
+    pub bits: Vec<AddressBitBias>,
+    errors: u64,
+}
+
+/// Errors needed before a bias is considered meaningful
+const MIN_ERRORS_FOR_BIAS: u64 = 32;
+/// Difference of shares and its statistical significance to flag the bit as biased
+const MIN_BIAS: f64 = 0.3;
+const MIN_BIAS_SIGMAS: f64 = 4.0;
+
+impl AddressBitStats {
+    /// `ones` counts errors with each bit of the value index set, among `errors` failing values
+    /// of `tested_values` starting at index `first_value`; byte offset is the index shifted by
+    /// `value_size_bits`. Bits constant over the tested values are never biased.
+    pub fn new(
+        ones: &[u64],
+        errors: u64,
+        first_value: u64,
+        tested_values: u64,
+        value_size_bits: u32,
+    ) -> Self {
+        let end = first_value + tested_values;
+        let bits = (0..ones.len())
+            .take_while(|bit| *bit < 63 && 1u64 << bit < end)
+            .map(|bit| {
+                //indices 0..limit with the bit set: full periods plus the partial one
+                let period = 1u64 << (bit + 1);
+                let half = 1u64 << bit;
+                let ones_below =
+                    |limit: u64| limit / period * half + (limit % period).saturating_sub(half);
+                let expected_ones = ones_below(end) - ones_below(first_value);
+                AddressBitBias {
+                    bit: bit + value_size_bits as usize,
+                    ones_share: ones[bit] as f64 / errors.max(1) as f64,
+                    expected_share: expected_ones as f64 / tested_values as f64,
+                }
+            })
+            .collect();
+        Self { bits, errors }
+    }
+
+    /// Bits with the share of ones strongly different from the expected one
+    pub fn biased(&self) -> Vec<&AddressBitBias> {
+        if self.errors < MIN_ERRORS_FOR_BIAS {
+            return Vec::new();
+        }
+        self.bits
+            .iter()
+            .filter(|bias| {
+                let deviation = bias.ones_share - bias.expected_share;
+                let sigma =
+                    (bias.expected_share * (1.0 - bias.expected_share) / self.errors as f64).sqrt();
+                deviation.abs() >= MIN_BIAS && deviation.abs() >= MIN_BIAS_SIGMAS * sigma
+            })
+            .collect()
+    }
+}
+
+/// Address bit statistics of all error blocks since the start of testing. Bits above the window
+/// size are the same for most values of a window, so a fault of such address line shows only as
+/// errors of different windows sharing the bit value.
+#[derive(Default)]
+pub struct AddressBitTally {
+    ones: Vec<u64>,
+    errors: u64,
+    windows: Vec<i64>,
+}
+
+/// Windows with errors needed before the tally is analysed, errors of a single window share
+/// its high address bits anyway
+const MIN_WINDOWS_FOR_TALLY: usize = 2;
+
+impl AddressBitTally {
+    pub fn add(&mut self, window_offset: i64, ones: &[u64], errors: u64) {
+        self.ones.resize(ones.len().max(self.ones.len()), 0);
+        for (total, count) in self.ones.iter_mut().zip(ones) {
+            *total += count;
+        }
+        self.errors += errors;
+        if !self.windows.contains(&window_offset) {
+            self.windows.push(window_offset);
+        }
+    }
+
+    pub fn window_count(&self) -> usize {
+        self.windows.len()
+    }
+
+    /// Statistics over the whole tested memory, None until errors of several windows are counted
+    pub fn stats(&self, tested_values: u64, value_size_bits: u32) -> Option<AddressBitStats> {
+        if self.windows.len() < MIN_WINDOWS_FOR_TALLY {
+            return None;
+        }
+        Some(AddressBitStats::new(
+            &self.ones,
+            self.errors,
+            0,
+            tested_values,
+            value_size_bits,
+        ))
+    }
+}
+
+impl fmt::Display for AddressBitStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, bias) in self.bits.iter().enumerate() {
+            if i % 8 == 0 && i != 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                " {:2}:{:3.0}/{:2.0}%",
+                bias.bit,
+                bias.ones_share * 100.0,
+                bias.expected_share * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of the confirmation of an error block: the failing window is re-read several times
+/// without rewriting, then rewritten and re-read
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ErrorPersistence {
+    /// Re-reads are clean, so the stored data is right and only the read went wrong
+    Transient,
+    /// Errors are read again even after rewriting, the cells don't hold the written value
+    Sticky,
+    /// Re-reads repeat the errors, but rewritten data is read correctly: wrong data was written
+    WritePath,
+}
+
+impl ErrorPersistence {
+    pub fn classify(reread_errors: &[i64], rewrite_errors: i64) -> Self {
+        if reread_errors.iter().all(|errors| *errors == 0) {
+            ErrorPersistence::Transient
+        } else if rewrite_errors > 0 {
+            ErrorPersistence::Sticky
+        } else {
+            ErrorPersistence::WritePath
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorPersistence::Transient => "transient",
+            ErrorPersistence::Sticky => "sticky",
+            ErrorPersistence::WritePath => "write_path",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ErrorPersistence::Transient => "TRANSIENT read glitch, stored data is right",
+            ErrorPersistence::Sticky => "STICKY errors, cells hold wrong values even after rewrite",
+            ErrorPersistence::WritePath => "WRITE-PATH errors, rewritten data is read correctly",
+        }
+    }
+}
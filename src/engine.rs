@@ -0,0 +1,2245 @@
+use crate::{
+    allocation,
+    backend::{AllocationError, Backend, Kernel},
+    close, diagnosis, events, fault, heatmap, inject,
+    modes::{self, TestMode},
+    output,
+    pattern::TestPattern,
+    schedule,
+    session::{ErrorBlock, Progress, TestConfig, TestObserver},
+    vulkan::VulkanBackend,
+};
+use byte_strings::c_str;
+use core::cmp::{max, min};
+use erupt::{
+    extensions::{ext_debug_utils, ext_memory_budget, ext_pci_bus_info},
+    vk, DeviceLoader, EntryLoader, InstanceLoader,
+};
+use std::{
+    cell::Cell,
+    env,
+    ffi::{c_void, CStr},
+    fmt,
+    io::Write,
+    mem, time,
+};
+
+struct CStrStaticPtr([*const std::os::raw::c_char; 1]);
+
+impl core::ops::Deref for CStrStaticPtr {
+    type Target = [*const std::os::raw::c_char; 1];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+unsafe impl Sync for CStrStaticPtr {}
+
+const VK_LOADER_DEBUG: &str = "VK_LOADER_DEBUG";
+const LAYER_KHRONOS_VALIDATION: &CStr = c_str!("VK_LAYER_KHRONOS_validation");
+static LAYER_KHRONOS_VALIDATION_ARRAY: CStrStaticPtr =
+    CStrStaticPtr([LAYER_KHRONOS_VALIDATION.as_ptr()]);
+const GB: f32 = (1024 * 1024 * 1024) as f32;
+pub(crate) const READ_SHADER: &[u32] = memtest_vulkan_build::compiled_vk_compute_spirv!(
+    r#"
+struct ErrorRecord
+{
+    vec_addr: u32,
+    expected: u32,
+    actual: u32,
+    xor_mask: u32
+}
+
+struct IOBuf
+{
+    err_bit1_idx: array<u32, 128>,
+    err_bitcount: array<u32, 128>,
+    mem_bitcount: array<u32, 32>,
+    err_addr_bit1: array<u32, 64>,
+    actual_ff: u32,
+    actual_max: u32,
+    actual_min: u32,
+    idx_max: u32,
+    idx_min: u32,
+    done_iter_or_err: u32,
+    iter: u32,
+    calc_param: u32,
+    first_elem: vec4<u32>,
+    err_capture_count: u32,
+    pattern: u32,
+    march_op: u32,
+    heat_base: u32,
+    heat_phase: u32,
+    heat_bucket_values: u32,
+    fault_model: u32,
+    fault_path: u32,
+    fault_addr: u32,
+    fault_bit: u32,
+    fault_rate: u32,
+    fault_seed: u32,
+    value_base_lo: u32,
+    value_base_hi: u32,
+    err_capture: array<ErrorRecord, 256>,
+    err_heat: array<u32, 1024>
+}
+
+@group(0) @binding(0) var<storage, read_write> io: IOBuf;
+@group(0) @binding(1) var<storage, read_write> test: array<vec4<u32>>;
+
+fn addr_value_by_index(i:u32)->vec4<u32>
+{
+    let effective_index_of_u32 = i * 4u + io.calc_param;
+    return vec4<u32>(effective_index_of_u32 + 1u, effective_index_of_u32 + 2u, effective_index_of_u32 + 3u, effective_index_of_u32 + 4u);
+}
+
+//values must match discriminants of TestPattern
+let PATTERN_ZEROS: u32 = 1u;
+let PATTERN_ONES: u32 = 2u;
+let PATTERN_CHECKERBOARD: u32 = 3u;
+let PATTERN_WALKING_ONES: u32 = 4u;
+let PATTERN_WALKING_ZEROS: u32 = 5u;
+let PATTERN_INVERTED_ADDRESS: u32 = 6u;
+
+fn test_value_by_index(i:u32)->vec4<u32>
+{
+    let addrs : vec4<u32> = addr_value_by_index(i);
+    let pattern = io.pattern;
+    if pattern == PATTERN_ZEROS {
+        return vec4<u32>(0u);
+    }
+    if pattern == PATTERN_ONES {
+        return vec4<u32>(0xFFFFFFFFu);
+    }
+    if pattern == PATTERN_CHECKERBOARD {
+        return select(vec4<u32>(0x55555555u), vec4<u32>(0xAAAAAAAAu), (addrs & vec4<u32>(1u)) != vec4<u32>(0u));
+    }
+    let walking_one : vec4<u32> = vec4<u32>(1u) << (addrs % 32u);
+    if pattern == PATTERN_WALKING_ONES {
+        return walking_one;
+    }
+    if pattern == PATTERN_WALKING_ZEROS {
+        return ~walking_one;
+    }
+    let shifts : vec4<u32> = addrs % 31u;
+    let rotated : vec4<u32> = (addrs << shifts) | (addrs >> (32u - shifts));
+    if pattern == PATTERN_INVERTED_ADDRESS {
+        return ~rotated;
+    }
+    return rotated;
+}
+
+
+//values must match FAULT_* constants in inject.rs
+let FAULT_BIT_FLIP: u32 = 1u;
+let FAULT_STUCK_AT_0: u32 = 2u;
+let FAULT_STUCK_AT_1: u32 = 3u;
+let FAULT_ALIAS: u32 = 4u;
+let FAULT_STORM: u32 = 5u;
+let FAULT_ON_WRITE: u32 = 1u;
+let FAULT_ON_READ: u32 = 2u;
+
+fn fault_hash(value: u32) -> u32 {
+    var x = value;
+    x = (x ^ (x >> 16u)) * 0x7FEB352Du;
+    x = (x ^ (x >> 15u)) * 0x846CA68Bu;
+    return x ^ (x >> 16u);
+}
+
+//vector index actually accessed instead of the given one by the injected aliasing fault
+fn fault_addr(addr: u32, path: u32) -> u32 {
+    if io.fault_model == FAULT_ALIAS && io.fault_path == path {
+        let aliased = addr ^ io.fault_addr;
+        if aliased < arrayLength(&test) {
+            return aliased;
+        }
+    }
+    return addr;
+}
+
+//value changed by the injected data fault
+fn fault_value(addr: u32, value: vec4<u32>, path: u32) -> vec4<u32> {
+    var result = value;
+    if io.fault_path != path {
+        return result;
+    }
+    let model = io.fault_model;
+    if model == FAULT_BIT_FLIP {
+        if addr == io.fault_addr / 4u {
+            result[io.fault_addr % 4u] ^= 1u << io.fault_bit;
+        }
+    } else if model == FAULT_STUCK_AT_0 {
+        result[io.fault_bit / 32u] &= ~(1u << (io.fault_bit % 32u));
+    } else if model == FAULT_STUCK_AT_1 {
+        result[io.fault_bit / 32u] |= 1u << (io.fault_bit % 32u);
+    } else if model == FAULT_STORM {
+        for(var i: u32 = 0u; i < 4u; i++) {
+            let hash = fault_hash((addr * 4u + i) ^ io.fault_seed);
+            if hash < io.fault_rate {
+                result[i] ^= 1u << (fault_hash(hash) % 32u);
+            }
+        }
+    }
+    return result;
+}
+
+let TEST_WINDOW_1D_MAX_GROUPS: u32 = 0x4000u;
+let TEST_WINDOW_READ_ADDR_ROTATION_GRANULARITY: u32 = 0x2000u;//don't inner-multiply by window size
+let ERR_CAPTURE_SIZE: u32 = 256u;
+let HEAT_MAP_BUCKETS: u32 = 1024u;
+
+//address bit counts of the workgroup, added to io once per workgroup, so failures of almost every
+//value don't make all invocations contend for the same 64 global counters.
+//Workgroup size is 64, so each invocation resets and flushes the counter of one address bit
+var<workgroup> wg_addr_bit1: array<atomic<u32>, 64>;
+
+fn reset_workgroup_addr_bits(local_idx: u32) {
+    atomicStore(&wg_addr_bit1[local_idx], 0u);
+    workgroupBarrier();
+}
+
+fn flush_workgroup_addr_bits(local_idx: u32) {
+    workgroupBarrier();
+    let count = atomicLoad(&wg_addr_bit1[local_idx]);
+    if count != 0u {
+        atomicAdd(&io.err_addr_bit1[local_idx], count);
+    }
+}
+
+//slow path, executed only on errors found
+fn report_errors(effective_addr: u32, actual_value: vec4<u32>, expected_value: vec4<u32>) {
+    for(var i: i32 = 0; i < 4; i++) {
+        let actual_u32 = actual_value[i];
+        let error_mask = actual_u32 ^ expected_value[i];
+        if error_mask == 0 {
+            continue;
+        }
+        let one_bits = countOneBits(error_mask);
+        if one_bits == 1
+        {
+            let bit_idx = firstLeadingBit(error_mask);
+            atomicAdd(&io.err_bit1_idx[u32(i) * 32u + bit_idx], 1u);
+        }
+        atomicAdd(&io.err_bitcount[u32(i) * 32u + one_bits % 32u], 1u);
+        let vec_addr: u32 = effective_addr * 4u + i;
+        atomicMax(&io.idx_max, vec_addr);
+        atomicMin(&io.idx_min, vec_addr);
+        if io.heat_bucket_values != 0u {
+            let heat_bucket = io.heat_base + (io.heat_phase + vec_addr) / io.heat_bucket_values;
+            if heat_bucket < HEAT_MAP_BUCKETS {
+                atomicAdd(&io.err_heat[heat_bucket], 1u);
+            }
+        }
+        //index of the value in the whole tested memory as 64-bit number
+        let value_lo = io.value_base_lo + vec_addr;
+        let value_hi = io.value_base_hi + select(0u, 1u, value_lo < vec_addr);
+        for(var addr_bit: u32 = 0u; addr_bit < 32u; addr_bit++) {
+            if ((value_lo >> addr_bit) & 1u) != 0u {
+                atomicAdd(&wg_addr_bit1[addr_bit], 1u);
+            }
+            if ((value_hi >> addr_bit) & 1u) != 0u {
+                atomicAdd(&wg_addr_bit1[32u + addr_bit], 1u);
+            }
+        }
+        let capture_idx = atomicAdd(&io.err_capture_count, 1u);
+        if capture_idx < ERR_CAPTURE_SIZE {
+            io.err_capture[capture_idx].vec_addr = vec_addr;
+            io.err_capture[capture_idx].expected = expected_value[i];
+            io.err_capture[capture_idx].actual = actual_u32;
+            io.err_capture[capture_idx].xor_mask = error_mask;
+        }
+        atomicMax(&io.done_iter_or_err, 0xFFFFFFFFu); //ERROR_STATUS
+        let actual_bits = countOneBits(actual_u32);
+        if actual_bits == 32
+        {
+            atomicAdd(&io.actual_ff, 1u);
+        }
+        else
+        {
+            atomicAdd(&io.mem_bitcount[actual_bits], 1u);
+            atomicMax(&io.actual_max, actual_u32);
+            atomicMin(&io.actual_min, actual_u32);
+        }
+    }
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn read(@builtin(global_invocation_id) global_invocation_id: vec3<u32>, @builtin(local_invocation_index) local_idx: u32) {
+    reset_workgroup_addr_bits(local_idx);
+    let effective_invocation_id: u32 = global_invocation_id[0] + global_invocation_id[1] * TEST_WINDOW_1D_MAX_GROUPS;
+    let addr_mod = effective_invocation_id % TEST_WINDOW_READ_ADDR_ROTATION_GRANULARITY;
+    let new_mod = (11 * effective_invocation_id + 999 * io.iter + io.calc_param +  7 * (effective_invocation_id / TEST_WINDOW_READ_ADDR_ROTATION_GRANULARITY)) % TEST_WINDOW_READ_ADDR_ROTATION_GRANULARITY;
+    let effective_addr = effective_invocation_id - addr_mod + new_mod; //make read order a bit rotated, not strictly sequential
+    let actual_value : vec4<u32> = fault_value(effective_addr, test[fault_addr(effective_addr, FAULT_ON_READ)], FAULT_ON_READ);
+    let expected_value : vec4<u32> = test_value_by_index(effective_addr);
+    if any(actual_value != expected_value) {
+        report_errors(effective_addr, actual_value, expected_value);
+    }
+    //assign done_iter_or_err only on specific index (performance reasons)
+    if effective_addr == 0 {
+        atomicMax(&io.done_iter_or_err, io.iter);
+    } else if effective_addr == 1 {
+        io.first_elem = expected_value;
+    }
+    flush_workgroup_addr_bits(local_idx);
+}
+
+@compute @workgroup_size(64, 1, 1)
+fn write(@builtin(global_invocation_id) global_invocation_id: vec3<u32>) {
+    let effective_invocation_id: u32 = global_invocation_id[0] + global_invocation_id[1] * TEST_WINDOW_1D_MAX_GROUPS;
+    //make global_invocation_id processing specific memory addr different on writing compared to reading
+    let TEST_WINDOW_SIZE_GRANULARITY: u32 = 64u * 8u * TEST_WINDOW_1D_MAX_GROUPS;//don't inner-multiply by window size
+    let proccessed_mod = effective_invocation_id % TEST_WINDOW_SIZE_GRANULARITY;
+    let proccessed_idx = effective_invocation_id + TEST_WINDOW_SIZE_GRANULARITY - 2 * proccessed_mod - 1;
+    test[fault_addr(proccessed_idx, FAULT_ON_WRITE)] = fault_value(proccessed_idx, test_value_by_index(proccessed_idx), FAULT_ON_WRITE);
+}
+
+//values must match MARCH_* constants in modes.rs
+let MARCH_READ_BACKGROUND: u32 = 1u;
+let MARCH_READ_INVERTED: u32 = 2u;
+let MARCH_WRITE_BACKGROUND: u32 = 4u;
+let MARCH_WRITE_INVERTED: u32 = 8u;
+let MARCH_DESCENDING: u32 = 16u;
+
+@compute @workgroup_size(64, 1, 1)
+fn march(@builtin(global_invocation_id) global_invocation_id: vec3<u32>, @builtin(local_invocation_index) local_idx: u32) {
+    reset_workgroup_addr_bits(local_idx);
+    let effective_invocation_id: u32 = global_invocation_id[0] + global_invocation_id[1] * TEST_WINDOW_1D_MAX_GROUPS;
+    let op = io.march_op;
+    var effective_addr = effective_invocation_id;
+    if (op & MARCH_DESCENDING) != 0u {
+        //workgroups are mostly started in order of their ids, so reversed addressing gives descending order
+        effective_addr = arrayLength(&test) - 1u - effective_invocation_id;
+    }
+    let background : vec4<u32> = test_value_by_index(effective_addr);
+    if (op & (MARCH_READ_BACKGROUND | MARCH_READ_INVERTED)) != 0u {
+        var expected_value : vec4<u32> = background;
+        if (op & MARCH_READ_INVERTED) != 0u {
+            expected_value = ~background;
+        }
+        let actual_value : vec4<u32> = fault_value(effective_addr, test[fault_addr(effective_addr, FAULT_ON_READ)], FAULT_ON_READ);
+        if any(actual_value != expected_value) {
+            report_errors(effective_addr, actual_value, expected_value);
+        }
+        if effective_addr == 0u {
+            atomicMax(&io.done_iter_or_err, io.iter);
+        }
+    }
+    if (op & MARCH_WRITE_BACKGROUND) != 0u {
+        test[fault_addr(effective_addr, FAULT_ON_WRITE)] = fault_value(effective_addr, background, FAULT_ON_WRITE);
+    } else if (op & MARCH_WRITE_INVERTED) != 0u {
+        test[fault_addr(effective_addr, FAULT_ON_WRITE)] = fault_value(effective_addr, ~background, FAULT_ON_WRITE);
+    }
+    flush_workgroup_addr_bits(local_idx);
+}
+"#
+);
+
+pub(crate) const WG_SIZE: i64 = 64;
+pub(crate) const VEC_SIZE: usize = 4; //vector processed by single workgroup item
+pub(crate) const ELEMENT_SIZE: i64 = std::mem::size_of::<u32>() as i64;
+pub(crate) const ELEMENT_BIT_SIZE: usize = (ELEMENT_SIZE * 8) as usize;
+pub(crate) const VEC_BIT_SIZE: usize = ELEMENT_BIT_SIZE * VEC_SIZE; //lanes of all vector components
+pub(crate) const ADDRESS_BIT_SIZE: usize = 64; //bits of the value index in the tested memory
+pub(crate) const TEST_WINDOW_1D_MAX_GROUPS: i64 = 0x4000;
+pub(crate) const ERR_CAPTURE_SIZE: usize = 256; //count of individual errors recorded by shader per dispatch
+const ERR_CAPTURE_DISPLAY: usize = 16; //count of individual errors displayed in non-verbose mode
+const TEST_WINDOW_SIZE_GRANULARITY: i64 =
+    VEC_SIZE as i64 * WG_SIZE * ELEMENT_SIZE * TEST_WINDOW_1D_MAX_GROUPS * 8_i64;
+const TEST_WINDOW_MAX_SIZE: i64 = 4 * 1024 * 1024 * 1024 - TEST_WINDOW_SIZE_GRANULARITY;
+const TEST_DATA_KEEP_FREE: i64 = 400 * 1024 * 1024;
+pub const MIN_WANTED_ALLOCATION: i64 = TEST_DATA_KEEP_FREE;
+
+/// Dispatch that didn't finish within the hang timeout
+#[derive(Debug)]
+pub struct GpuHang {
+    pub pipeline: &'static str,
+    pub window_idx: i64,
+    pub test_offset: i64,
+    pub iteration: u32,
+    pub timeout: time::Duration,
+}
+
+impl fmt::Display for GpuHang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "GPU HANG: {} dispatch for window {} at 0x{:X} on iteration {} not finished in {:.1}s",
+            self.pipeline,
+            self.window_idx,
+            self.test_offset,
+            self.iteration,
+            self.timeout.as_secs_f32()
+        )
+    }
+}
+
+impl std::error::Error for GpuHang {}
+
+/// ERROR_DEVICE_LOST returned for a test dispatch
+#[derive(Debug)]
+pub struct DeviceLost {
+    pub context: &'static str,
+    pub pipeline: &'static str,
+    pub window_idx: i64,
+    pub iteration: u32,
+}
+
+impl fmt::Display for DeviceLost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ERROR_DEVICE_LOST in context {} of {} dispatch for window {} on iteration {}",
+            self.context, self.pipeline, self.window_idx, self.iteration
+        )
+    }
+}
+
+impl std::error::Error for DeviceLost {}
+
+/// Failure of the worker process during initialization, the console process retries the test
+/// with lower memory size instead of reporting it
+#[derive(Debug)]
+pub struct AllocationFailed {
+    pub reason: Box<dyn std::error::Error>,
+}
+
+impl fmt::Display for AllocationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "initialization failed, retry with lower memory: {}",
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for AllocationFailed {}
+
+/// The probe worker allocated the memory, the console process only wanted to know that
+#[derive(Debug)]
+pub struct ProbeSucceeded;
+
+impl fmt::Display for ProbeSucceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "memory size probe succeeded")
+    }
+}
+
+impl std::error::Error for ProbeSucceeded {}
+
+#[derive(Default)]
+struct U64HexDebug(i64);
+
+impl fmt::Debug for U64HexDebug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "0x{:X}", self.0)
+    }
+}
+
+#[derive(Default)]
+struct DriverVersionDebug(u32);
+
+impl fmt::Debug for DriverVersionDebug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let upper10bits = self.0 >> 22;
+        if upper10bits > 2 {
+            // NVIDIA (0x75a04180 = 470.129.06) and intel-mesa-on-linux (0x5402006 = 21.2.6) driver versioning
+            return write!(f, "v{}(0x{:X})", upper10bits, self.0);
+        }
+        let upper18bits = self.0 >> 14;
+        if upper18bits > 2 && upper18bits < 500 {
+            //intel-on-windows driver versioning (0x19453c = [30.0.]101.1340)
+            return write!(f, "v{}(0x{:X})", upper18bits, self.0);
+        }
+        if self.0 < 64 {
+            //basic small number versioning like llvm
+            return write!(f, "ver{}", self.0);
+        }
+        // don't parse AMD versioning like "0x8000E6"
+        write!(f, "0x{:X}", self.0)
+    }
+}
+
+#[derive(Copy, Clone)]
+pub(crate) struct MostlyZeroArr<const LEN: usize>(pub(crate) [u32; LEN]);
+
+impl<const LEN: usize> fmt::Display for MostlyZeroArr<LEN> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if LEN < 8 {
+            for i in 0..LEN {
+                write!(f, "0x{:08X} ", self.0[i])?;
+            }
+            return Ok(());
+        }
+        for i in 0..LEN {
+            let next_row = i / 16;
+            //lane tables are shown only by non-zero rows, each one labeled by vector component
+            let lane_table = LEN > ELEMENT_BIT_SIZE;
+            if (next_row > 0 || lane_table)
+                && self.0[next_row * 16..next_row * 16 + 16]
+                    .iter()
+                    .all(|v| v == &0u32)
+            {
+                continue; //skip entire line of zeroes
+            }
+            if lane_table && i % 16 == 0 {
+                let component = ["x", "y", "z", "w"][i / ELEMENT_BIT_SIZE % VEC_SIZE];
+                write!(f, " {component}.0x{:X}? ", i % ELEMENT_BIT_SIZE / 16)?;
+            } else if i % 16 == 0 && i != 0 {
+                write!(f, "   0x{:X}? ", i / 16)?;
+            }
+            let vali = self.0[i];
+            if vali > 999999u32 {
+                write!(f, "{:3}m", vali / 1000000u32)?;
+            } else if vali > 9999u32 {
+                write!(f, "{:3}k", vali / 1000u32)?;
+            } else if vali > 0 {
+                write!(f, "{:4}", vali)?;
+            } else {
+                write!(f, "    ")?; //zero
+            }
+            if i % 16 == 15 {
+                writeln!(f)?;
+            } else if i % 4 == 3 {
+                write!(f, "|")?;
+            } else if i % 4 == 1 {
+                write!(f, " ")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MostlyZeroArr<VEC_BIT_SIZE> {
+    /// Sums the lane tables of all vector components into a single component table
+    fn fold_components(&self) -> MostlyZeroArr<ELEMENT_BIT_SIZE> {
+        let mut folded = MostlyZeroArr::<ELEMENT_BIT_SIZE>::default();
+        for (i, value) in self.0.iter().enumerate() {
+            folded.0[i % ELEMENT_BIT_SIZE] += value;
+        }
+        folded
+    }
+}
+
+impl<const LEN: usize> std::default::Default for MostlyZeroArr<LEN> {
+    fn default() -> Self {
+        Self([0; LEN])
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub(crate) struct IOBuf {
+    pub(crate) err_bit1_idx: MostlyZeroArr<VEC_BIT_SIZE>,
+    pub(crate) err_bitcount: MostlyZeroArr<VEC_BIT_SIZE>,
+    pub(crate) mem_bitcount: MostlyZeroArr<ELEMENT_BIT_SIZE>,
+    pub(crate) err_addr_bit1: MostlyZeroArr<ADDRESS_BIT_SIZE>,
+    pub(crate) actual_ff: u32,
+    pub(crate) actual_max: u32,
+    pub(crate) actual_min: u32,
+    pub(crate) idx_max: u32,
+    pub(crate) idx_min: u32,
+    pub(crate) done_iter_or_err: u32,
+    pub(crate) iter: u32,
+    pub(crate) calc_param: u32,
+    pub(crate) first_elem: MostlyZeroArr<VEC_SIZE>,
+    pub(crate) err_capture_count: u32,
+    pub(crate) pattern: u32,
+    pub(crate) march_op: u32,
+    pub(crate) heat_base: u32,
+    pub(crate) heat_phase: u32,
+    pub(crate) heat_bucket_values: u32,
+    pub(crate) fault_model: u32,
+    pub(crate) fault_path: u32,
+    pub(crate) fault_addr: u32,
+    pub(crate) fault_bit: u32,
+    pub(crate) fault_rate: u32,
+    pub(crate) fault_seed: u32,
+    pub(crate) value_base_lo: u32,
+    pub(crate) value_base_hi: u32,
+    pub(crate) err_capture: ErrorCapture,
+    pub(crate) err_heat: MostlyZeroArr<{ heatmap::HEAT_MAP_BUCKETS }>,
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub(crate) struct ErrorRecord {
+    pub(crate) vec_addr: u32,
+    pub(crate) expected: u32,
+    pub(crate) actual: u32,
+    pub(crate) xor_mask: u32,
+}
+
+#[derive(Copy, Clone)]
+pub(crate) struct ErrorCapture(pub(crate) [ErrorRecord; ERR_CAPTURE_SIZE]);
+
+impl std::default::Default for ErrorCapture {
+    fn default() -> Self {
+        Self([ErrorRecord::default(); ERR_CAPTURE_SIZE])
+    }
+}
+
+impl fmt::Display for IOBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "values range: 0x{:08X}..=0x{:08X}   FFFFFFFF-like count:{}    bit-level stats table:",
+            self.actual_max, self.actual_min, self.actual_ff
+        )?;
+        writeln!(
+            f,
+            "         0x0 0x1  0x2 0x3| 0x4 0x5  0x6 0x7| 0x8 0x9  0xA 0xB| 0xC 0xD  0xE 0xF"
+        )?;
+        write!(f, "SinglIdx{}", self.err_bit1_idx.fold_components())?;
+        write!(f, "TogglCnt{}", self.err_bitcount.fold_components())?;
+        write!(f, "1sInValu{}", self.mem_bitcount)?;
+        //the same tables split by vector components, so a faulty data line can be located
+        writeln!(
+            f,
+            "per-lane view, row z.0x1? is 0x10..0x1F of the vector z component:"
+        )?;
+        write!(f, "LaneSngl\n{}", self.err_bit1_idx)?;
+        write!(f, "LaneTggl\n{}", self.err_bitcount)?;
+        Ok(())
+    }
+}
+
+impl IOBuf {
+    fn add_to_event(&self, event: events::Event) -> events::Event {
+        event
+            .num("iteration", self.iter)
+            .num("calc_param", self.calc_param)
+            .str("pattern", &self.test_pattern().to_string())
+            .num("actual_min", self.actual_min)
+            .num("actual_max", self.actual_max)
+            .num("actual_ff", self.actual_ff)
+            .nums("single_bit_index", self.err_bit1_idx.fold_components().0)
+            .nums("toggle_count", self.err_bitcount.fold_components().0)
+            .nums("lane_single_bit_index", self.err_bit1_idx.0)
+            .nums("lane_toggle_count", self.err_bitcount.0)
+            .nums("address_bit_ones", self.err_addr_bit1.0)
+            .nums("ones_in_value", self.mem_bitcount.0)
+    }
+    fn for_initial_iteration(pattern: TestPattern) -> Self {
+        let mut result = Self {
+            pattern: pattern as u32,
+            ..Self::default()
+        };
+        result.prepare_next_iter_write();
+        result
+    }
+    fn test_pattern(&self) -> TestPattern {
+        TestPattern::from_index(self.pattern).unwrap_or(TestPattern::Address)
+    }
+    fn prepare_next_iter_write(&mut self) {
+        self.reset_errors();
+        self.iter += 1;
+        self.set_calc_param_for_starting_window();
+    }
+    fn set_calc_param_for_starting_window(&mut self) {
+        self.calc_param = self.iter.wrapping_mul(0x100107);
+    }
+    fn reset_errors(&mut self) {
+        *self = IOBuf {
+            iter: self.iter,
+            calc_param: self.calc_param,
+            pattern: self.pattern,
+            march_op: self.march_op,
+            idx_max: u32::MIN,
+            idx_min: u32::MAX,
+            actual_max: u32::MIN,
+            actual_min: u32::MAX,
+            ..Self::default()
+        };
+    }
+    fn get_error_addresses_and_count(
+        &self,
+        buf_offset: i64,
+    ) -> Option<(std::ops::RangeInclusive<U64HexDebug>, i64)> {
+        if self.done_iter_or_err == self.iter {
+            None
+        } else {
+            let total_errors = self.mem_bitcount.0.iter().sum::<u32>() as i64;
+            Some((
+                std::ops::RangeInclusive::<U64HexDebug>::new(
+                    U64HexDebug(buf_offset + self.idx_min as i64 * ELEMENT_SIZE),
+                    U64HexDebug(buf_offset + (self.idx_max + 1) as i64 * ELEMENT_SIZE - 1),
+                ),
+                total_errors,
+            ))
+        }
+    }
+    fn diagnose(&self, tested_values: i64) -> Vec<diagnosis::Finding> {
+        diagnosis::diagnose(&diagnosis::ErrorStats {
+            single_bit_lanes: &self.err_bit1_idx.0,
+            toggle_count: &self.err_bitcount.fold_components().0,
+            ones_in_value: &self.mem_bitcount.0,
+            all_ones_count: self.actual_ff,
+            tested_values,
+        })
+    }
+    fn address_bit_ones(&self) -> Vec<u64> {
+        self.err_addr_bit1
+            .0
+            .iter()
+            .map(|count| *count as u64)
+            .collect()
+    }
+    fn address_bit_stats(
+        &self,
+        test_offset: i64,
+        tested_values: i64,
+    ) -> diagnosis::AddressBitStats {
+        diagnosis::AddressBitStats::new(
+            &self.address_bit_ones(),
+            self.err_bitcount.0.iter().map(|count| *count as u64).sum(),
+            (test_offset / ELEMENT_SIZE) as u64,
+            tested_values as u64,
+            ELEMENT_SIZE.trailing_zeros(),
+        )
+    }
+    /// Individual errors recorded by shader, ordered by address
+    fn captured_errors(&self) -> Vec<ErrorRecord> {
+        let captured_count = min(self.err_capture_count as usize, ERR_CAPTURE_SIZE);
+        let mut captured = self.err_capture.0[..captured_count].to_vec();
+        captured.sort_by_key(|record| record.vec_addr);
+        captured
+    }
+    fn write_captured_errors(
+        &self,
+        f: &mut impl Write,
+        buf_offset: i64,
+        verbose: bool,
+    ) -> std::io::Result<()> {
+        let captured = self.captured_errors();
+        writeln!(
+            f,
+            "Individual errors: {} captured of {} in this check",
+            captured.len(),
+            self.err_capture_count
+        )?;
+        let display_count = if verbose {
+            captured.len()
+        } else {
+            min(captured.len(), ERR_CAPTURE_DISPLAY)
+        };
+        for record in &captured[..display_count] {
+            writeln!(
+                f,
+                "  {:?}: expected 0x{:08X} actual 0x{:08X} xor 0x{:08X}",
+                U64HexDebug(buf_offset + record.vec_addr as i64 * ELEMENT_SIZE),
+                record.expected,
+                record.actual,
+                record.xor_mask
+            )?;
+        }
+        if display_count < captured.len() {
+            writeln!(
+                f,
+                "  ...{} more captured errors are in the verbose output",
+                captured.len() - display_count
+            )?;
+        }
+        Ok(())
+    }
+    fn add_captured_errors_to_event(&self, event: events::Event, buf_offset: i64) -> events::Event {
+        let captured = self.captured_errors();
+        event
+            .num("captured_total", self.err_capture_count)
+            .nums(
+                "captured_addresses",
+                captured
+                    .iter()
+                    .map(|record| buf_offset + record.vec_addr as i64 * ELEMENT_SIZE),
+            )
+            .nums("captured_expected", captured.iter().map(|r| r.expected))
+            .nums("captured_actual", captured.iter().map(|r| r.actual))
+            .nums("captured_xor", captured.iter().map(|r| r.xor_mask))
+    }
+    fn check_vec_first(&self) -> Result<(), Box<dyn std::error::Error>> {
+        const TEST_IDX: u32 = 1;
+        let addr: u32 = TEST_IDX * VEC_SIZE as u32 + self.calc_param + 1u32;
+        let expected = self.test_pattern().value(addr);
+        if expected != self.first_elem.0[0] {
+            return Err(format!(
+                "unexpected calculated value, maybe shader execution is broken: {} 0x{:08X}",
+                self, expected
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+pub(crate) trait MapErrStr {
+    type ValueType;
+    fn err_as_str(self) -> Result<Self::ValueType, Box<dyn std::error::Error>>;
+    fn err_as_str_context(
+        self,
+        context: &str,
+    ) -> Result<Self::ValueType, Box<dyn std::error::Error>>;
+}
+
+impl<T> MapErrStr for std::result::Result<T, erupt::LoaderError> {
+    type ValueType = T;
+    fn err_as_str(self) -> Result<Self::ValueType, Box<dyn std::error::Error>> {
+        self.map_err(|res| {
+            let msg = match res {
+                erupt::LoaderError::SymbolNotAvailable => {
+                    "SymbolNotAvailable in Loader".to_string()
+                }
+                erupt::LoaderError::VulkanError(result) => format!("{}", result),
+            } + " while getting "
+                + std::any::type_name::<Self::ValueType>();
+            msg.into()
+        })
+    }
+    fn err_as_str_context(
+        self,
+        context: &str,
+    ) -> Result<Self::ValueType, Box<dyn std::error::Error>> {
+        self.map_err(|res| {
+            let msg = match res {
+                erupt::LoaderError::SymbolNotAvailable => {
+                    "SymbolNotAvailable in Loader".to_string()
+                }
+                erupt::LoaderError::VulkanError(result) => format!("{}", result),
+            } + " while getting "
+                + std::any::type_name::<Self::ValueType>()
+                + " in context "
+                + context;
+            msg.into()
+        })
+    }
+}
+
+impl<T> MapErrStr for erupt::utils::VulkanResult<T> {
+    type ValueType = T;
+    fn err_as_str(self) -> Result<Self::ValueType, Box<dyn std::error::Error>> {
+        let result = self.result();
+        result.map_err(|res| {
+            let msg =
+                res.to_string() + " while getting " + std::any::type_name::<Self::ValueType>();
+            msg.into()
+        })
+    }
+    fn err_as_str_context(
+        self,
+        context: &str,
+    ) -> Result<Self::ValueType, Box<dyn std::error::Error>> {
+        let result = self.result();
+        result.map_err(|res| {
+            let msg = res.to_string()
+                + " while getting "
+                + std::any::type_name::<Self::ValueType>()
+                + " in context "
+                + context;
+            msg.into()
+        })
+    }
+}
+/// Worker gives up on failures during initialization, so the console process retries the test
+/// with lower memory size
+fn retry_with_lower_memory(
+    config: &TestConfig,
+    err: Box<dyn std::error::Error>,
+) -> Box<dyn std::error::Error> {
+    if config.worker
+        && !close::check_any_bits_set(close::fetch_status(), close::app_status::INITED_OK)
+        && !err.is::<AllocationFailed>()
+    {
+        return Box::new(AllocationFailed { reason: err });
+    }
+    err
+}
+
+unsafe extern "system" fn debug_callback(
+    _message_severity: vk::DebugUtilsMessageSeverityFlagBitsEXT,
+    _message_types: vk::DebugUtilsMessageTypeFlagsEXT,
+    p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
+    _p_user_data: *mut c_void,
+) -> vk::Bool32 {
+    eprintln!(
+        "{}",
+        CStr::from_ptr((*p_callback_data).p_message).to_string_lossy()
+    );
+
+    vk::FALSE
+}
+/// Sets the tested size to the free memory of the selected device unless the size is set already
+pub fn try_fill_default_mem_budget<Writer: std::io::Write>(
+    loaded_devices: &LoadedDevices,
+    config: &mut TestConfig,
+    log_dupler: &mut output::LogDupler<Writer>,
+) {
+    let selected_index = config.effective_index();
+    let LoadedDevices(instance, _, _, devices_labeled_from_1) = &loaded_devices;
+
+    if config.verbose() {
+        let _ = writeln!(
+            log_dupler,
+            "Loading memory info for selected device index {selected_index}...",
+        );
+    }
+    if config.max_test_bytes > 0 || selected_index >= devices_labeled_from_1.len() {
+        return;
+    }
+
+    let mut budget_structure: ext_memory_budget::PhysicalDeviceMemoryBudgetPropertiesEXT =
+        Default::default();
+
+    let mut memory_props = unsafe {
+        instance.get_physical_device_memory_properties(
+            devices_labeled_from_1[selected_index].physical_device,
+        )
+    };
+
+    let mut budget_request = *vk::PhysicalDeviceMemoryProperties2Builder::new();
+
+    if devices_labeled_from_1[selected_index].has_vk_1_1 {
+        budget_request.p_next = &mut budget_structure
+            as *mut ext_memory_budget::PhysicalDeviceMemoryBudgetPropertiesEXT
+            as *mut c_void;
+        let memory_props2 = unsafe {
+            instance.get_physical_device_memory_properties2(
+                devices_labeled_from_1[selected_index].physical_device,
+                Some(budget_request),
+            )
+        };
+        memory_props = memory_props2.memory_properties;
+    }
+    for i in 0..memory_props.memory_heap_count as usize {
+        if config.verbose() {
+            let _ = writeln!(
+                log_dupler,
+                "heap size {:4.1}GB budget {:4.1}GB usage {:4.1}GB flags={:#?}",
+                memory_props.memory_heaps[i].size as f32 / GB,
+                budget_structure.heap_budget[i] as f32 / GB,
+                budget_structure.heap_usage[i] as f32 / GB,
+                memory_props.memory_heaps[i].flags,
+            );
+        }
+        if !memory_props.memory_heaps[i]
+            .flags
+            .contains(vk::MemoryHeapFlags::DEVICE_LOCAL)
+        {
+            continue;
+        }
+        let mut heap_free = memory_props.memory_heaps[i].size as i64;
+        let usage = budget_structure.heap_usage[i] as i64;
+        if usage > 0 && usage < heap_free {
+            heap_free -= usage;
+        }
+        let budget = budget_structure.heap_budget[i] as i64;
+        if budget > 0 {
+            heap_free = min(heap_free, budget);
+        }
+        config.max_test_bytes = max(config.max_test_bytes, heap_free - TEST_DATA_KEEP_FREE);
+    }
+}
+
+fn prepare_and_test_device<Writer: std::io::Write>(
+    instance: &erupt::InstanceLoader,
+    selected: NamedComputeDevice,
+    config: &TestConfig,
+    log_dupler: &mut output::LogDupler<Writer>,
+    observer: &mut dyn TestObserver,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let queue_create_info = vec![vk::DeviceQueueCreateInfoBuilder::new()
+        .queue_family_index(selected.queue_family_index)
+        .queue_priorities(&[1.0])];
+
+    //fault details after device loss are queried only if the driver can report them
+    let device_fault =
+        selected.has_vk_1_1 && fault::is_supported(instance, selected.physical_device);
+    let device_extensions = [fault::EXT_DEVICE_FAULT_EXTENSION_NAME.as_ptr()];
+    let mut fault_features = fault::PhysicalDeviceFaultFeatures::new(true);
+    let mut device_create_info =
+        *vk::DeviceCreateInfoBuilder::new().queue_create_infos(&queue_create_info);
+    if device_fault {
+        device_create_info.enabled_extension_count = device_extensions.len() as u32;
+        device_create_info.pp_enabled_extension_names = device_extensions.as_ptr();
+        device_create_info.p_next = fault_features.as_p_next();
+    }
+
+    let memory_props =
+        unsafe { instance.get_physical_device_memory_properties(selected.physical_device) };
+    let mut max_allocation_size = i64::MAX;
+    if selected.has_vk_1_1 {
+        let mut maintenance3_props = vk::PhysicalDeviceMaintenance3Properties::default();
+        let mut props_request = *vk::PhysicalDeviceProperties2Builder::new();
+        props_request.p_next =
+            &mut maintenance3_props as *mut vk::PhysicalDeviceMaintenance3Properties as *mut c_void;
+        unsafe {
+            instance.get_physical_device_properties2(selected.physical_device, Some(props_request))
+        };
+        if maintenance3_props.max_memory_allocation_size > 0 {
+            max_allocation_size = min(
+                maintenance3_props.max_memory_allocation_size,
+                i64::MAX as u64,
+            ) as i64;
+        }
+    }
+    let device =
+        unsafe { DeviceLoader::new(instance, selected.physical_device, &device_create_info) }?;
+    let backend = VulkanBackend::new(
+        &device,
+        selected.queue_family_index,
+        memory_props,
+        max_allocation_size,
+        config.hang_timeout,
+        config.verbose(),
+        log_dupler,
+    )?;
+    let result = test_device(&backend, log_dupler, &selected.label, config, observer);
+    if let Err(e) = &result {
+        if e.is::<DeviceLost>() {
+            log_device_fault(&device, device_fault, log_dupler);
+        }
+        if e.is::<GpuHang>() {
+            //the hung dispatch may still use the objects, so they are left until the process exit
+            mem::forget(backend);
+            mem::forget(device);
+            return result;
+        }
+    }
+    drop(backend);
+    unsafe { device.destroy_device(None) };
+    result
+}
+
+/// Tests the selected device of the loaded ones in this process. Testing stops on the first
+/// runtime error, which is returned; found memory errors are reported to the log, the events and
+/// the observer, and raise the RUNTIME_ERRORS status bit.
+pub fn test_loaded_device<Writer: std::io::Write>(
+    mut loaded_devices: LoadedDevices,
+    config: &TestConfig,
+    log_dupler: &mut output::LogDupler<Writer>,
+    observer: &mut dyn TestObserver,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let LoadedDevices(instance, _, _, devices_labeled_from_1) = &mut loaded_devices;
+    let selected_index = config.effective_index();
+    if selected_index >= devices_labeled_from_1.len() {
+        return Err("No device at given index".into());
+    }
+
+    if config.max_test_bytes == 0 {
+        return Err("Failed determining memory budget".into());
+    }
+
+    let result = prepare_and_test_device(
+        instance,
+        devices_labeled_from_1.swap_remove(selected_index),
+        config,
+        log_dupler,
+        observer,
+    );
+    if matches!(&result, Err(e) if e.is::<GpuHang>()) {
+        //instance can't be destroyed while the device is still in use
+        mem::forget(loaded_devices);
+    } else {
+        let _ = writeln!(log_dupler, "Destroying vk instance...");
+        drop(loaded_devices);
+    }
+    result
+}
+
+/// Writes fault records of the lost device to the log next to the error reports
+fn log_device_fault<Writer: std::io::Write>(
+    device: &erupt::DeviceLoader,
+    device_fault: bool,
+    log_dupler: &mut output::LogDupler<Writer>,
+) {
+    match fault::query(device, device_fault) {
+        Ok(report) => {
+            events::Event::new("device_fault")
+                .str("description", &report.description)
+                .strs(
+                    "addresses",
+                    report.addresses.iter().map(|address| address.to_string()),
+                )
+                .strs(
+                    "vendor_faults",
+                    report.vendor_faults.iter().map(|fault| fault.to_string()),
+                )
+                .emit();
+            let _ = writeln!(log_dupler, "{report}");
+        }
+        Err(reason) => {
+            let _ = writeln!(log_dupler, "Device fault details unavailable: {reason}");
+        }
+    }
+}
+
+pub(crate) fn test_device<B: Backend, Writer: std::io::Write>(
+    backend: &B,
+    log_dupler: &mut output::LogDupler<Writer>,
+    selected_label: &str,
+    config: &TestConfig,
+    observer: &mut dyn TestObserver,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if config.max_test_bytes < MIN_WANTED_ALLOCATION {
+        return Err("requested test size is smaller than minimum wanted".into());
+    }
+
+    //all preparations except huge buffer allocation done. Now allocate huge buffer as a last step to minize chance of allocation failure for small structures
+
+    let mut layout;
+    let mut max_allocation_size = backend.max_allocation_size();
+    let mut size_search = allocation::SizeSearch::new(
+        config.max_test_bytes,
+        MIN_WANTED_ALLOCATION,
+        config.alloc_precision,
+    );
+    let mut warn_on_budget_alloc_fail = true;
+    let injected_fault = Cell::new(config.inject);
+    let current_iteration = Cell::new(1u32);
+    let dispatch_count = Cell::new(0u32);
+    let mut execute_wait_queue;
+
+    //The error state before all allocation tries
+    let mut last_err: Box<dyn std::error::Error> =
+        "No heap reports memory enough for testing".into();
+    'memsize: loop {
+        backend.free_allocations();
+
+        let allocation_size = size_search.size();
+        layout = match allocation::AllocationLayout::plan(
+            allocation_size,
+            max_allocation_size,
+            TEST_WINDOW_MAX_SIZE,
+            TEST_WINDOW_SIZE_GRANULARITY,
+        ) {
+            Some(layout) => layout,
+            None => return Err(last_err),
+        };
+        if config.verbose() {
+            let _ = writeln!(
+                log_dupler,
+                "Trying {:7.3}GB buffer in {} allocation(s)...",
+                allocation_size as f32 / GB,
+                layout.allocation_count()
+            );
+        }
+        let mut allocations_result = Ok(());
+        for allocation_idx in 0..layout.allocation_count() {
+            match backend.allocate(layout.allocation_size(allocation_idx)) {
+                Ok(()) => {}
+                Err(AllocationError::Memory(_)) if layout.windows_per_allocation > 1 => {
+                    //some drivers fail huge allocations below the reported limit, so try smaller
+                    //allocations of the same total size before reducing the tested size
+                    max_allocation_size = layout.windows_per_allocation / 2 * layout.window_size;
+                    if config.verbose() {
+                        let _ = writeln!(
+                            log_dupler,
+                            "Failed allocating {:5.1}GB at once, retrying with allocations of at most {:5.1}GB",
+                            layout.allocation_size(allocation_idx) as f32 / GB,
+                            max_allocation_size as f32 / GB
+                        );
+                    }
+                    continue 'memsize;
+                }
+                Err(AllocationError::Memory(err) | AllocationError::Other(err)) => {
+                    allocations_result = Err(retry_with_lower_memory(config, err));
+                    break;
+                }
+            }
+        }
+        match allocations_result {
+            Err(err) => last_err = err,
+            Ok(()) => {
+                execute_wait_queue = |test_offset: i64,
+                                      kernel: Kernel,
+                                      access: inject::Access|
+                 -> Result<(), Box<dyn std::error::Error>> {
+                    let heat_layout = heatmap::BucketLayout::new(layout.total_size(), ELEMENT_SIZE);
+                    let (heat_base, heat_phase) = heat_layout.window_params(test_offset);
+                    dispatch_count.set(dispatch_count.get().wrapping_add(1));
+                    backend.update_io(&mut |io| {
+                        io.heat_base = heat_base;
+                        io.heat_phase = heat_phase;
+                        io.heat_bucket_values = heat_layout.bucket_values as u32;
+                        let value_base = (test_offset / ELEMENT_SIZE) as u64;
+                        io.value_base_lo = value_base as u32;
+                        io.value_base_hi = (value_base >> 32) as u32;
+                        io.fault_model = inject::FAULT_NONE;
+                        if let Some(fault) = injected_fault.get() {
+                            fault.set_params(
+                                io,
+                                test_offset,
+                                layout.window_size,
+                                current_iteration.get(),
+                                access,
+                                dispatch_count.get().wrapping_mul(0x9E3779B9),
+                            );
+                        }
+                    });
+                    backend
+                        .dispatch(kernel, &layout, test_offset)
+                        .map_err(|e| {
+                            if e.is::<GpuHang>() || e.is::<DeviceLost>() {
+                                e
+                            } else {
+                                retry_with_lower_memory(config, e)
+                            }
+                        })?;
+                    events::heartbeat();
+                    Ok(())
+                };
+                backend.write_io(&IOBuf::for_initial_iteration(config.patterns[0]));
+                //try to do initial memory fill to verify that allocation is really usable
+                let mut overall_exec_result = Ok(());
+                'window: for window_idx in 0..layout.window_count {
+                    let test_offset = layout.window_size * window_idx;
+                    if let Err(e) =
+                        execute_wait_queue(test_offset, Kernel::Write, inject::Access::Test)
+                    {
+                        overall_exec_result = Err(e);
+                        break 'window;
+                    }
+                    if let Err(e) =
+                        execute_wait_queue(test_offset, Kernel::Read, inject::Access::Test)
+                    {
+                        overall_exec_result = Err(e);
+                        break 'window;
+                    }
+                }
+                match overall_exec_result {
+                    Err(e) => last_err = e,
+                    Ok(()) => match size_search.succeeded() {
+                        None => break 'memsize,
+                        Some(bigger_size) => {
+                            if config.verbose() {
+                                let _ = writeln!(
+                                    log_dupler,
+                                    "Allocated {:5.1}GB, trying bigger {:5.1}GB",
+                                    allocation_size as f32 / GB,
+                                    bigger_size as f32 / GB
+                                );
+                            }
+                            continue 'memsize;
+                        }
+                    },
+                }
+            }
+        }
+        if last_err.is::<DeviceLost>() {
+            //lost device is a symptom of faulty hardware, not of too big allocation
+            return Err(last_err);
+        }
+        if config.verbose() {
+            let _ = writeln!(
+                log_dupler,
+                "Don't testing {:5.1}GB due to error: {}",
+                allocation_size as f32 / GB,
+                last_err
+            );
+        }
+        if config.worker {
+            return Err(retry_with_lower_memory(config, last_err));
+        }
+        if warn_on_budget_alloc_fail {
+            warn_on_budget_alloc_fail = false;
+            let _ = writeln!(log_dupler, "Failed allocating {:5.1}GB, trying to use smaller size. More system memory can help.", allocation_size as f32 / GB);
+        }
+        if size_search.failed().is_none() {
+            return Err(last_err);
+        }
+    }
+    if config.verbose() {
+        let _ = writeln!(
+            log_dupler,
+            "Chosen test size {:5.1}GB after {} allocation attempt(s)",
+            layout.total_size() as f32 / GB,
+            size_search.attempts
+        );
+    }
+    if config.probe {
+        //the console process only wanted to know whether this size is usable
+        return Err(Box::new(ProbeSucceeded));
+    }
+
+    let test_window_size = layout.window_size;
+    let test_window_count = layout.window_count;
+    let allocation_indices = 0..layout.allocation_count();
+    events::Event::new("allocation")
+        .str("device", selected_label)
+        .num("bytes", layout.total_size())
+        .num("windows", test_window_count)
+        .num("window_bytes", test_window_size)
+        .num("memory_type", backend.memory_type())
+        .nums(
+            "allocation_bases",
+            allocation_indices
+                .clone()
+                .map(|idx| layout.allocation_base(idx)),
+        )
+        .nums(
+            "allocation_bytes",
+            allocation_indices
+                .clone()
+                .map(|idx| layout.allocation_size(idx)),
+        )
+        .emit();
+    if layout.allocation_count() > 1 {
+        for idx in allocation_indices {
+            let _ = writeln!(
+                log_dupler,
+                "Allocation {:2}: base 0x{:09X} size {:5.1}GB",
+                idx,
+                layout.allocation_base(idx),
+                layout.allocation_size(idx) as f32 / GB
+            );
+        }
+    }
+    if config.verbose() {
+        let _ = writeln!(
+            log_dupler,
+            "Test memory size {:5.1}GB   {}",
+            layout.total_size() as f32 / GB,
+            backend.describe_memory()
+        );
+    }
+
+    // allow write bugs emulation for testing purposes
+    if injected_fault.get().is_none() {
+        let emulate_write_bugs_iteration = env::var("MEMTEST_VULKAN_EMULATE_WRITE_BUG_ITERATION")
+            .ok()
+            .and_then(|s| s.parse::<u32>().ok())
+            .filter(|iteration| *iteration > 0);
+        injected_fault.set(emulate_write_bugs_iteration.map(inject::FaultSpec::legacy_write_bug));
+    }
+    if let Some(fault) = injected_fault.get() {
+        events::Event::new("fault_injection")
+            .str("fault", &fault.to_string())
+            .emit();
+        writeln!(log_dupler, "Injecting fault {fault}")?;
+    }
+    let iter_count = config.max_iterations.unwrap_or(100000000); //by default exit after several days of testing
+    let mut written_bytes = 0i64;
+    let mut read_bytes = 0i64;
+    let mut next_report_duration = time::Duration::from_secs(0);
+    let extended_test_report_duration = time::Duration::from_secs(30);
+    let mut reports_before_standard_done = 12i32;
+    let mut write_duration = time::Duration::ZERO;
+    let mut buffer_in = IOBuf::for_initial_iteration(config.patterns[0]);
+    let mut heat_map = heatmap::HeatMap::new(heatmap::BucketLayout::new(
+        layout.total_size(),
+        ELEMENT_SIZE,
+    ));
+    let mut address_tally = diagnosis::AddressBitTally::default();
+    let mut idle_duration = time::Duration::ZERO;
+    let test_start = time::Instant::now();
+    let mut start = test_start;
+    //re-reads the failing window without rewriting, then rewrites and re-reads it. `check` and
+    //`rewrite` are kernels with their input verifying and writing the data left in the window
+    let confirm_errors = |log_dupler: &mut output::LogDupler<Writer>,
+                          check: (Kernel, &IOBuf),
+                          rewrite: (Kernel, &IOBuf),
+                          test_offset: i64|
+     -> Result<(), Box<dyn std::error::Error>> {
+        let (check_kernel, check_input) = check;
+        let (rewrite_kernel, rewrite_input) = rewrite;
+        let read_window = |access| -> Result<i64, Box<dyn std::error::Error>> {
+            backend.write_io(check_input);
+            execute_wait_queue(test_offset, check_kernel, access)?;
+            let buffer_out = backend.read_io();
+            Ok(buffer_out
+                .get_error_addresses_and_count(test_offset)
+                .map_or(0, |(_, total_errors)| total_errors))
+        };
+        let mut reread_errors = Vec::new();
+        for _ in 0..config.confirm_reads {
+            reread_errors.push(read_window(inject::Access::Reread)?);
+        }
+        backend.write_io(rewrite_input);
+        execute_wait_queue(test_offset, rewrite_kernel, inject::Access::Rewrite)?;
+        let rewrite_errors = read_window(inject::Access::Test)?;
+        let persistence = diagnosis::ErrorPersistence::classify(&reread_errors, rewrite_errors);
+        events::Event::new("confirmation")
+            .num("address", test_offset)
+            .num("iteration", check_input.iter)
+            .str("persistence", persistence.name())
+            .nums("reread_errors", &reread_errors)
+            .num("rewrite_errors", rewrite_errors)
+            .emit();
+        writeln!(
+            log_dupler,
+            "Confirmation: re-reads without rewrite found {} errors, after rewrite 0x{:X} errors - {}",
+            reread_errors
+                .iter()
+                .map(|errors| format!("0x{errors:X}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            rewrite_errors,
+            persistence.description()
+        )?;
+        Ok(())
+    };
+    //applies single march element to all windows in the element's direction
+    let run_march_element = |log_dupler: &mut output::LogDupler<Writer>,
+                             observer: &mut dyn TestObserver,
+                             heat_map: &mut heatmap::HeatMap,
+                             address_tally: &mut diagnosis::AddressBitTally,
+                             buffer_in: &mut IOBuf,
+                             element: &modes::MarchElement,
+                             mode_name: &str|
+     -> Result<(), Box<dyn std::error::Error>> {
+        let windows_order: Vec<i64> = if element.descending() {
+            (0..test_window_count).rev().collect()
+        } else {
+            (0..test_window_count).collect()
+        };
+        for window_idx in windows_order {
+            buffer_in.set_calc_param_for_starting_window();
+            buffer_in.calc_param += window_idx as u32 * 0x81_u32;
+            buffer_in.march_op = element.op;
+            backend.write_io(buffer_in);
+            let test_offset = test_window_size * window_idx;
+            execute_wait_queue(test_offset, Kernel::March, inject::Access::Test)?;
+            if element.reads() {
+                let buffer_out = backend.read_io();
+                report_error_block(
+                    log_dupler,
+                    observer,
+                    heat_map,
+                    address_tally,
+                    &buffer_out,
+                    test_offset,
+                    &layout,
+                    mode_name,
+                    config,
+                )?;
+                if config.confirm_reads > 0
+                    && buffer_out
+                        .get_error_addresses_and_count(test_offset)
+                        .is_some()
+                {
+                    let mut check_input = *buffer_in;
+                    check_input.march_op = element.check_op();
+                    let mut rewrite_input = *buffer_in;
+                    rewrite_input.march_op = element.rewrite_op();
+                    confirm_errors(
+                        log_dupler,
+                        (Kernel::March, &check_input),
+                        (Kernel::March, &rewrite_input),
+                        test_offset,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    };
+    let mut schedule_cursor = schedule::ScheduleCursor::new(&config.schedule);
+    let mut phase_start = test_start;
+    //the testing is run by the closure, so errors counted before its failure are reported too
+    let tested = (|| -> Result<(), Box<dyn std::error::Error>> {
+        for iteration in 1..=iter_count {
+            current_iteration.set(iteration as u32);
+            backend.set_iteration(iteration as u32);
+            if !config.schedule.is_empty() {
+                let mut phase_started = iteration == 1;
+                if !phase_started && phase_start.elapsed() >= schedule_cursor.current().duration {
+                    schedule_cursor.advance();
+                    phase_started = true;
+                }
+                //phases without load are executed between iterations until the next load phase
+                while phase_started {
+                    let phase = schedule_cursor.current();
+                    phase_start = time::Instant::now();
+                    events::Event::new("phase")
+                        .num("index", schedule_cursor.index())
+                        .str("phase", &phase.to_string())
+                        .emit();
+                    if config.verbose() {
+                        writeln!(log_dupler, "Schedule phase {}", phase)?;
+                    }
+                    match phase.kind {
+                        schedule::PhaseKind::Load => {
+                            phase_started = false;
+                            continue;
+                        }
+                        schedule::PhaseKind::Idle => {
+                            idle_duration += keep_gpu_idle(phase.duration, test_start, config);
+                        }
+                        schedule::PhaseKind::Check => {
+                            //the re-read window keeps data written once at start regardless of iteration
+                            let window_input = IOBuf::for_initial_iteration(config.patterns[0]);
+                            backend.write_io(&window_input);
+                            execute_wait_queue(0, Kernel::Read, inject::Access::Reread)?;
+                            let buffer_out = backend.read_io();
+                            report_error_block(
+                                log_dupler,
+                                observer,
+                                &mut heat_map,
+                                &mut address_tally,
+                                &buffer_out,
+                                0,
+                                &layout,
+                                &format!("SCHEDULE_CHECK after {}", schedule_cursor.previous()),
+                                config,
+                            )?;
+                            buffer_out.check_vec_first()?;
+                            if config.confirm_reads > 0
+                                && buffer_out.get_error_addresses_and_count(0).is_some()
+                            {
+                                confirm_errors(
+                                    log_dupler,
+                                    (Kernel::Read, &window_input),
+                                    (Kernel::Write, &window_input),
+                                    0,
+                                )?;
+                            }
+                            read_bytes += test_window_size;
+                        }
+                    }
+                    schedule_cursor.advance();
+                }
+            }
+            buffer_in.pattern = config.pattern_for_iteration(iteration) as u32;
+            if config.mode == TestMode::MarchC {
+                for element in &modes::MARCH_C_MINUS {
+                    let element_start = time::Instant::now();
+                    run_march_element(
+                        log_dupler,
+                        observer,
+                        &mut heat_map,
+                        &mut address_tally,
+                        &mut buffer_in,
+                        element,
+                        &format!("MARCH_C- {}", element.name),
+                    )?;
+                    let element_bytes = test_window_size * test_window_count;
+                    if element.writes() {
+                        written_bytes += element_bytes;
+                    }
+                    if element.reads() {
+                        read_bytes += element_bytes;
+                    } else {
+                        write_duration += element_start.elapsed();
+                    }
+                }
+            } else if config.mode == TestMode::Retention {
+                for (fill, verify) in &modes::RETENTION_PHASES {
+                    let fill_start = time::Instant::now();
+                    run_march_element(
+                        log_dupler,
+                        observer,
+                        &mut heat_map,
+                        &mut address_tally,
+                        &mut buffer_in,
+                        fill,
+                        fill.name,
+                    )?;
+                    written_bytes += test_window_size * test_window_count;
+                    write_duration += fill_start.elapsed();
+                    let hold = keep_gpu_idle(config.hold_duration, test_start, config);
+                    idle_duration += hold;
+                    run_march_element(
+                        log_dupler,
+                        observer,
+                        &mut heat_map,
+                        &mut address_tally,
+                        &mut buffer_in,
+                        verify,
+                        &format!(
+                            "RETENTION {} after {:.1}s hold",
+                            verify.name,
+                            hold.as_secs_f32()
+                        ),
+                    )?;
+                    read_bytes += test_window_size * test_window_count;
+                }
+            } else {
+                backend.write_io(&buffer_in);
+                let write_start = time::Instant::now();
+                for window_idx in 1..test_window_count {
+                    let test_offset = test_window_size * window_idx;
+                    backend.update_io(&mut |io| {
+                        io.calc_param = buffer_in.calc_param + window_idx as u32 * 0x81_u32;
+                    });
+                    execute_wait_queue(test_offset, Kernel::Write, inject::Access::Test)?;
+                }
+                written_bytes += test_window_size * (test_window_count - 1);
+                write_duration += write_start.elapsed();
+                let mut last_buffer_out: IOBuf;
+                for window_idx in 0..test_window_count {
+                    let reread_mode_for_this_win = window_idx == 0;
+                    buffer_in.set_calc_param_for_starting_window();
+                    buffer_in.calc_param += window_idx as u32 * 0x81_u32;
+                    let window_input = if reread_mode_for_this_win {
+                        IOBuf::for_initial_iteration(config.patterns[0])
+                    } else {
+                        buffer_in
+                    };
+                    backend.write_io(&window_input);
+                    let test_offset = test_window_size * window_idx;
+                    execute_wait_queue(
+                        test_offset,
+                        Kernel::Read,
+                        if reread_mode_for_this_win {
+                            inject::Access::Reread
+                        } else {
+                            inject::Access::Test
+                        },
+                    )?;
+                    last_buffer_out = backend.read_io();
+                    report_error_block(
+                        log_dupler,
+                        observer,
+                        &mut heat_map,
+                        &mut address_tally,
+                        &last_buffer_out,
+                        test_offset,
+                        &layout,
+                        if reread_mode_for_this_win {
+                            "NEXT_RE_READ"
+                        } else {
+                            "INITIAL_READ"
+                        },
+                        config,
+                    )?;
+                    last_buffer_out.check_vec_first()?;
+                    if config.confirm_reads > 0
+                        && last_buffer_out
+                            .get_error_addresses_and_count(test_offset)
+                            .is_some()
+                    {
+                        confirm_errors(
+                            log_dupler,
+                            (Kernel::Read, &window_input),
+                            (Kernel::Write, &window_input),
+                            test_offset,
+                        )?;
+                    }
+                }
+                read_bytes += test_window_size * test_window_count;
+            }
+            let elapsed = start.elapsed();
+            let mut stop_reason = if close::close_requested() {
+                Some(StopReason::UserInterruption)
+            } else if config.duration_exceeded(test_start) {
+                Some(StopReason::DurationLimit)
+            } else if iteration == iter_count {
+                Some(StopReason::IterationLimit)
+            } else {
+                None
+            };
+            if elapsed > next_report_duration || stop_reason.is_some() {
+                let write_secs = write_duration.as_secs_f32();
+                let passed_secs = elapsed.as_secs_f32() - write_secs - idle_duration.as_secs_f32();
+                let write_speed_gbps = if write_secs > 0.0001 {
+                    written_bytes as f32 / GB / write_secs
+                } else {
+                    0f32
+                };
+                let check_speed_gbps = if passed_secs > 0.0001 {
+                    read_bytes as f32 / GB / passed_secs
+                } else {
+                    0f32
+                };
+                observer.on_progress(&Progress {
+                    iteration,
+                    elapsed,
+                    written_bytes,
+                    write_speed_gbps,
+                    checked_bytes: read_bytes,
+                    check_speed_gbps,
+                });
+                let second1 = time::Duration::from_secs(1);
+                if next_report_duration.is_zero() {
+                    writeln!(log_dupler, "Standard 5-minute test of {}", selected_label)?;
+                    next_report_duration = second1; //2nd report after 1 second
+                } else if next_report_duration == second1 {
+                    close::raise_status_bit(close::app_status::INITED_OK);
+                    next_report_duration = second1 * 5; //3rd report after 5 seconds
+                } else {
+                    next_report_duration = extended_test_report_duration; //all later reports
+                }
+                if reports_before_standard_done == 0 {
+                    let has_errors = close::check_any_bits_set(
+                        close::fetch_status(),
+                        close::app_status::RUNTIME_ERRORS,
+                    );
+                    events::Event::new("standard_test_done")
+                        .bool("passed", !has_errors)
+                        .emit();
+                    if config.standard_only {
+                        match has_errors {
+                            true => {
+                                writeln!(log_dupler, "Standard 5-minute test fail - ERRORS FOUND")
+                            }
+                            false => writeln!(log_dupler, "Standard 5-minute test PASSed!"),
+                        }?;
+                        stop_reason.get_or_insert(StopReason::StandardTestDone);
+                    } else {
+                        match has_errors {
+                        true => writeln!(log_dupler, "Standard 5-minute test fail - ERRORS FOUND"),
+                        false => writeln!(log_dupler, "Standard 5-minute test PASSed! Just press Ctrl+C unless you plan long test run."),
+                    }?;
+                        writeln!(
+                        log_dupler,
+                        "Extended endless test started; testing more than 2 hours is usually unneeded"
+                    )?;
+                        writeln!(
+                            log_dupler,
+                            "use Ctrl+C to stop it when you decide it's enough"
+                        )?;
+                    }
+                } else {
+                    events::Event::new("report")
+                        .num("iteration", iteration)
+                        .float("seconds", elapsed.as_secs_f64())
+                        .num("written_bytes", written_bytes)
+                        .float("write_speed_gbps", write_speed_gbps as f64)
+                        .num("checked_bytes", read_bytes)
+                        .float("check_speed_gbps", check_speed_gbps as f64)
+                        .emit();
+                    writeln!(log_dupler, "{:7} iteration. Passed {:7.4} seconds  written:{:7.1}GB{:6.1}GB/sec        checked:{:7.1}GB{:6.1}GB/sec", iteration, elapsed.as_secs_f32(), written_bytes as f32 / GB, write_speed_gbps, read_bytes as f32 / GB, check_speed_gbps)?;
+                }
+                reports_before_standard_done -= 1;
+                if reports_before_standard_done == 0 {
+                    // The last iteration before report has a sleep before it to test hot gpu behaviour
+                    // in a situation of load pause and low-performance memory frequency.
+                    // Heartbeats keep the console from taking the pause for a hang
+                    keep_gpu_idle(next_report_duration / 2, test_start, config);
+                }
+                written_bytes = 0i64;
+                read_bytes = 0i64;
+                write_duration = time::Duration::ZERO;
+                idle_duration = time::Duration::ZERO;
+                start = time::Instant::now();
+            }
+            if let Some(reason) = stop_reason {
+                if reason != StopReason::UserInterruption {
+                    //at least one full iteration was checked, so the test is considered initialized even if
+                    //the limit was reached before the usual initialization report
+                    close::raise_status_bit(close::app_status::INITED_OK);
+                }
+                events::Event::new("stopped")
+                    .str("reason", reason.description())
+                    .num("iteration", iteration)
+                    .emit();
+                let _ = writeln!(log_dupler, "{}, testing stopped", reason.description());
+                report_heat_map(log_dupler, &heat_map, config);
+                break;
+            }
+            if close::heat_map_requested() {
+                writeln!(log_dupler, "{heat_map}")?;
+            }
+            buffer_in.prepare_next_iter_write();
+        }
+        Ok(())
+    })();
+    if let Err(e) = tested {
+        report_heat_map(log_dupler, &heat_map, config);
+        return Err(e);
+    }
+    // Cleanup & Destruction
+    backend.wait_idle()?;
+    backend.free_allocations();
+    close::declare_exit_due_timeout();
+    Ok(())
+}
+
+/// Prints the heat map of the run if there were errors and exports it if requested
+fn report_heat_map<Writer: std::io::Write>(
+    log_dupler: &mut output::LogDupler<Writer>,
+    heat_map: &heatmap::HeatMap,
+    config: &TestConfig,
+) {
+    if heat_map.total() > 0 {
+        events::Event::new("heat_map")
+            .num("bucket_bytes", heat_map.layout().bucket_bytes())
+            .nums("counts", heat_map.counts())
+            .emit();
+        let _ = writeln!(log_dupler, "{heat_map}");
+    }
+    let path = match &config.heat_map_file {
+        Some(path) => path,
+        None => return,
+    };
+    let export_result = std::fs::File::create(path).and_then(|file| {
+        let mut out = std::io::BufWriter::new(file);
+        heat_map.write_ppm(&mut out)?;
+        out.flush()
+    });
+    match export_result {
+        Ok(()) => {
+            let _ = writeln!(log_dupler, "Error heat map image saved to {path}");
+        }
+        Err(e) => {
+            let _ = writeln!(
+                log_dupler,
+                "Failed saving error heat map image to {path}: {e}"
+            );
+        }
+    }
+}
+
+/// Sleeps for the given time, but stays responsive to stop requests and duration limit.
+/// Returns the actual idle time.
+fn keep_gpu_idle(
+    duration: time::Duration,
+    test_start: time::Instant,
+    config: &TestConfig,
+) -> time::Duration {
+    let idle_start = time::Instant::now();
+    while idle_start.elapsed() < duration
+        && !close::close_pending()
+        && !config.duration_exceeded(test_start)
+    {
+        std::thread::sleep(min(
+            duration.saturating_sub(idle_start.elapsed()),
+            time::Duration::from_millis(100),
+        ));
+        events::heartbeat();
+    }
+    idle_start.elapsed()
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum StopReason {
+    UserInterruption,
+    DurationLimit,
+    IterationLimit,
+    StandardTestDone,
+}
+
+impl StopReason {
+    fn description(self) -> &'static str {
+        match self {
+            StopReason::UserInterruption => "received user interruption",
+            StopReason::DurationLimit => "test duration limit reached",
+            StopReason::IterationLimit => "test iteration limit reached",
+            StopReason::StandardTestDone => "standard test done",
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn report_error_block<Writer: std::io::Write>(
+    log_dupler: &mut output::LogDupler<Writer>,
+    observer: &mut dyn TestObserver,
+    heat_map: &mut heatmap::HeatMap,
+    address_tally: &mut diagnosis::AddressBitTally,
+    buffer_out: &IOBuf,
+    test_offset: i64,
+    layout: &allocation::AllocationLayout,
+    mode: &str,
+    config: &TestConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (error_range, total_errors) = match buffer_out.get_error_addresses_and_count(test_offset) {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    close::raise_status_bit(close::app_status::RUNTIME_ERRORS);
+    heat_map.add(&buffer_out.err_heat.0);
+    let test_elems = layout.window_size / ELEMENT_SIZE;
+    let (allocation_idx, _) = layout.locate(test_offset);
+    let findings = buffer_out.diagnose(test_elems);
+    let address_bits = buffer_out.address_bit_stats(test_offset, test_elems);
+    let biased_bits = address_bits.biased();
+    address_tally.add(
+        test_offset,
+        &buffer_out.address_bit_ones(),
+        buffer_out
+            .err_bitcount
+            .0
+            .iter()
+            .map(|count| *count as u64)
+            .sum(),
+    );
+    let tally_bits = address_tally.stats(
+        (layout.total_size() / ELEMENT_SIZE) as u64,
+        ELEMENT_SIZE.trailing_zeros(),
+    );
+    //bits biased inside the window are reported by the block itself
+    let tally_biased: Vec<_> = tally_bits
+        .iter()
+        .flat_map(|stats| stats.biased())
+        .filter(|bias| {
+            biased_bits
+                .iter()
+                .all(|block_bias| block_bias.bit != bias.bit)
+        })
+        .collect();
+    buffer_out
+        .add_captured_errors_to_event(
+            buffer_out.add_to_event(events::Event::new("error")),
+            test_offset,
+        )
+        .str("mode", mode)
+        .num("total_errors", total_errors)
+        .num("tested_elements", test_elems)
+        .num("address_first", error_range.start().0)
+        .num("address_last", error_range.end().0)
+        .num("allocation", allocation_idx)
+        .num("allocation_base", layout.allocation_base(allocation_idx))
+        .strs(
+            "diagnosis",
+            findings.iter().map(|finding| finding.description.as_str()),
+        )
+        .nums(
+            "diagnosis_confidence",
+            findings.iter().map(|finding| finding.confidence),
+        )
+        .nums(
+            "biased_address_bits",
+            biased_bits.iter().map(|bias| bias.bit),
+        )
+        .nums(
+            "tally_biased_address_bits",
+            tally_biased.iter().map(|bias| bias.bit),
+        )
+        .emit();
+    observer.on_error(&ErrorBlock {
+        mode,
+        iteration: buffer_out.iter,
+        pattern: buffer_out.test_pattern(),
+        address_first: error_range.start().0,
+        address_last: error_range.end().0,
+        total_errors,
+        tested_elements: test_elems,
+        allocation: allocation_idx,
+        diagnosis: &findings,
+    });
+    write!(
+        log_dupler,
+        "Error found. Mode {}, total errors 0x{:X} out of 0x{:X} ({:2.8}%)\nErrors address range: {:?}",
+        mode,
+        total_errors,
+        test_elems,
+        total_errors as f64 / test_elems as f64 * 100.0f64,
+        error_range,
+    )?;
+    if layout.allocation_count() > 1 {
+        write!(
+            log_dupler,
+            " in allocation {} with base 0x{:09X}",
+            allocation_idx,
+            layout.allocation_base(allocation_idx)
+        )?;
+    }
+    writeln!(
+        log_dupler,
+        "  iteration:{} pattern:{}\n{}",
+        buffer_out.iter,
+        buffer_out.test_pattern(),
+        buffer_out
+    )?;
+    if config.verbose() {
+        writeln!(
+            log_dupler,
+            "Test offset bits of failing values, share of 1s and expected share:"
+        )?;
+        writeln!(log_dupler, "{address_bits}")?;
+    }
+    for bias in &biased_bits {
+        writeln!(
+            log_dupler,
+            "Biased address bit {} ({:?} of test offset): {bias}",
+            bias.bit,
+            U64HexDebug(1 << bias.bit),
+        )?;
+    }
+    for bias in &tally_biased {
+        writeln!(
+            log_dupler,
+            "Biased address bit {} ({:?} of test offset) over errors of {} windows since start: {bias}",
+            bias.bit,
+            U64HexDebug(1 << bias.bit),
+            address_tally.window_count(),
+        )?;
+    }
+    if !findings.is_empty() {
+        writeln!(log_dupler, "Diagnosis, most likely first:")?;
+        for finding in &findings {
+            writeln!(log_dupler, "  {finding}")?;
+        }
+    }
+    buffer_out.write_captured_errors(log_dupler, test_offset, config.verbose())?;
+    Ok(())
+}
+
+pub struct NamedComputeDevice {
+    pub label: String,
+    physical_device: vk::PhysicalDevice,
+    queue_family_index: u32,
+    has_vk_1_1: bool,
+}
+
+fn load_instance<Writer: std::io::Write>(
+    verbosity: u8,
+    log_dupler: &mut output::LogDupler<Writer>,
+) -> Result<
+    (
+        erupt::InstanceLoader,
+        erupt::EntryLoader,
+        vk::DebugUtilsMessengerEXT,
+    ),
+    Box<dyn std::error::Error>,
+> {
+    let verbose = verbosity > 0;
+    let override_vk_loader_debug = env::var_os(VK_LOADER_DEBUG).is_none();
+    if verbose && override_vk_loader_debug {
+        env::set_var(VK_LOADER_DEBUG, "error,warn");
+    }
+
+    let mut entry = EntryLoader::new()?;
+    if verbose {
+        let _ = writeln!(
+            log_dupler,
+            "Verbose mode enabled (level {verbosity}). Vulkan instance {}.{}.{}",
+            vk::api_version_major(entry.instance_version()),
+            vk::api_version_minor(entry.instance_version()),
+            vk::api_version_patch(entry.instance_version())
+        );
+        for (idx, prop) in unsafe { entry.enumerate_instance_layer_properties(None) }
+            .err_as_str()
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+        {
+            if idx == 0 {
+                let _ = writeln!(log_dupler, "Available: ");
+            } else {
+                let _ = write!(log_dupler, ", ");
+            }
+
+            let _ = write!(log_dupler, "{}", unsafe {
+                CStr::from_ptr(prop.layer_name.as_ptr())
+                    .to_str()
+                    .unwrap_or("Invalid property_name")
+            });
+        }
+        let _ = writeln!(log_dupler);
+        for (idx, ext) in unsafe { entry.enumerate_instance_extension_properties(None, None) }
+            .err_as_str()
+            .unwrap_or_default()
+            .iter()
+            .enumerate()
+        {
+            if idx == 0 {
+                let _ = write!(log_dupler, "Extensions: ");
+            } else {
+                let _ = write!(log_dupler, ", ");
+            }
+
+            let _ = write!(log_dupler, "{}", unsafe {
+                CStr::from_ptr(ext.extension_name.as_ptr())
+                    .to_str()
+                    .unwrap_or("Invalid extension_name")
+            });
+        }
+        let _ = writeln!(log_dupler);
+        let _ = writeln!(log_dupler);
+    }
+
+    let instance_extensions = vec![ext_debug_utils::EXT_DEBUG_UTILS_EXTENSION_NAME];
+
+    let app_info = vk::ApplicationInfoBuilder::new().api_version(vk::API_VERSION_1_1);
+    let instance_create_info = vk::InstanceCreateInfoBuilder::new()
+        .enabled_extension_names(&instance_extensions)
+        .enabled_layer_names(&*LAYER_KHRONOS_VALIDATION_ARRAY)
+        .application_info(&app_info);
+
+    let mut messenger = vk::DebugUtilsMessengerEXT::null();
+    match unsafe {
+        InstanceLoader::new(&entry, &instance_create_info)
+            .err_as_str_context("instance with validation")
+    } {
+        Ok(instance_with_validation) => {
+            let mut severity = ext_debug_utils::DebugUtilsMessageSeverityFlagsEXT::WARNING_EXT
+                | ext_debug_utils::DebugUtilsMessageSeverityFlagsEXT::ERROR_EXT;
+            if verbose {
+                severity |= ext_debug_utils::DebugUtilsMessageSeverityFlagsEXT::INFO_EXT;
+            }
+            if verbosity > 1 {
+                //lists all extensions, very verbose
+                severity |= ext_debug_utils::DebugUtilsMessageSeverityFlagsEXT::VERBOSE_EXT;
+            }
+            let create_info = ext_debug_utils::DebugUtilsMessengerCreateInfoEXTBuilder::new()
+                .message_severity(severity)
+                .message_type(ext_debug_utils::DebugUtilsMessageTypeFlagsEXT::all())
+                .pfn_user_callback(Some(debug_callback));
+            messenger = unsafe {
+                instance_with_validation.create_debug_utils_messenger_ext(&create_info, None)
+            }
+            .result()
+            .unwrap_or_default();
+            return Ok((instance_with_validation, entry, messenger));
+        }
+        Err(e) => {
+            if verbose {
+                let _ = writeln!(log_dupler, "Not using validation layers due to {e}");
+            }
+        }
+    }
+
+    //fallback creation without validation and extensions
+    let simple_instance_try = unsafe {
+        InstanceLoader::new(
+            &entry,
+            &vk::InstanceCreateInfoBuilder::new().application_info(&app_info),
+        )
+    }
+    .err_as_str_context("instance. Try specifying icd.json via VK_DRIVER_FILES env var");
+    match simple_instance_try {
+        Ok(instance) => Ok((instance, entry, messenger)),
+        Err(e) => {
+            if !override_vk_loader_debug {
+                return Err(e);
+            }
+            drop(entry);
+            //retry instance creation with loader debuf enabled
+            env::set_var(VK_LOADER_DEBUG, "all");
+            entry = EntryLoader::new()?;
+            let debug_instance_try = unsafe {
+                InstanceLoader::new(
+                    &entry,
+                    &vk::InstanceCreateInfoBuilder::new().application_info(&app_info),
+                )
+            }
+            .map_err(|_second_error_ignired| e)?;
+            Ok((debug_instance_try, entry, messenger))
+        }
+    }
+}
+//InstanceLoader must be dropped after EntryLoader
+pub struct LoadedDevices(
+    erupt::InstanceLoader,
+    #[allow(dead_code)] erupt::EntryLoader,
+    vk::DebugUtilsMessengerEXT,
+    Vec<NamedComputeDevice>,
+);
+
+impl Drop for LoadedDevices {
+    fn drop(&mut self) {
+        let LoadedDevices(instance, _, messenger, _) = self;
+        unsafe {
+            if !messenger.is_null() {
+                instance.destroy_debug_utils_messenger_ext(*messenger, None);
+            }
+            instance.destroy_instance(None);
+        }
+    }
+}
+impl LoadedDevices {
+    /// Compute-capable devices, the label of each one starts with its number from 1
+    pub fn devices(&self) -> &[NamedComputeDevice] {
+        &self.3
+    }
+}
+
+/// Loads the Vulkan instance and lists compute-capable devices: discrete first, then integrated,
+/// then others, each group ordered by the PCI bus in descending order
+pub fn list_devices_ordered_labaled_from_1<Writer: std::io::Write>(
+    verbosity: u8,
+    log_dupler: &mut output::LogDupler<Writer>,
+) -> Result<LoadedDevices, Box<dyn std::error::Error>> {
+    let verbose = verbosity > 0;
+    let (instance, entry, messenger) = load_instance(verbosity, log_dupler)?;
+    let mut compute_capable_devices: Vec<_> = unsafe { instance.enumerate_physical_devices(None) }
+        .err_as_str()?
+        .into_iter()
+        .filter_map(|physical_device| unsafe {
+            let queue_family = match instance
+                .get_physical_device_queue_family_properties(physical_device, None)
+                .into_iter()
+                .position(|properties| properties.queue_flags.contains(vk::QueueFlags::COMPUTE))
+            {
+                Some(queue_family) => queue_family as u32,
+                None => return None,
+            };
+
+            let mut pci_props_structure: ext_pci_bus_info::PhysicalDevicePCIBusInfoPropertiesEXT =
+                Default::default();
+            let mut properties = instance.get_physical_device_properties(physical_device);
+            let effective_version = (
+                vk::api_version_major(properties.api_version),
+                vk::api_version_minor(properties.api_version),
+            );
+
+            //older vulkan implementations like broadcom on RaspberryPi lacks vk_1_1 support even if application requested it
+            let has_vk_1_1 = effective_version >= (1, 1);
+            if has_vk_1_1 {
+                let mut pci_structure_request = *vk::PhysicalDeviceProperties2Builder::new();
+                pci_structure_request.p_next = &mut pci_props_structure
+                    as *mut ext_pci_bus_info::PhysicalDevicePCIBusInfoPropertiesEXT
+                    as *mut c_void;
+
+                properties = instance
+                    .get_physical_device_properties2(physical_device, Some(pci_structure_request))
+                    .properties;
+            }
+            let memory_props = instance.get_physical_device_memory_properties(physical_device);
+
+            let mut max_local_heap_size = 0i64;
+            for i in 0..memory_props.memory_heap_count as usize {
+                if !memory_props.memory_heaps[i]
+                    .flags
+                    .contains(vk::MemoryHeapFlags::DEVICE_LOCAL)
+                {
+                    continue;
+                }
+                max_local_heap_size = max(
+                    max_local_heap_size,
+                    memory_props.memory_heaps[i].size as i64,
+                );
+            }
+
+            Some((
+                physical_device,
+                queue_family,
+                properties,
+                max_local_heap_size,
+                pci_props_structure,
+                has_vk_1_1,
+            ))
+        })
+        .collect();
+    compute_capable_devices.sort_by_key(|(_, _, props, _, pci_props, _)| {
+        let negative_bus_for_reverse_ordering = -(pci_props.pci_bus as i32);
+        match props.device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => (0, negative_bus_for_reverse_ordering),
+            vk::PhysicalDeviceType::INTEGRATED_GPU => (1, negative_bus_for_reverse_ordering),
+            _ => (2, negative_bus_for_reverse_ordering),
+        }
+    });
+    let mut numbered_devices: Vec<NamedComputeDevice> = Vec::new();
+    for (i, d) in compute_capable_devices.iter().enumerate() {
+        let props = d.2;
+        let pci_props = d.4;
+        let api_info = if verbose {
+            std::format!(
+                "API {}.{}.{}  {:?}",
+                vk::api_version_major(props.api_version),
+                vk::api_version_minor(props.api_version),
+                vk::api_version_patch(props.api_version),
+                DriverVersionDebug(props.driver_version),
+            )
+        } else {
+            String::new()
+        };
+        numbered_devices.push(NamedComputeDevice {
+            label: std::format!(
+                "{}: Bus=0x{:02X}:{:02X} DevId=0x{:04X} {api_info}  {}GB {}",
+                i + 1,
+                pci_props.pci_bus,
+                pci_props.pci_device,
+                props.device_id,
+                (d.3 as f32 / GB).ceil(),
+                unsafe {
+                    CStr::from_ptr(props.device_name.as_ptr())
+                        .to_str()
+                        .unwrap_or("Invalid device_name")
+                }
+            ),
+            physical_device: d.0,
+            queue_family_index: d.1,
+            has_vk_1_1: d.5,
+        });
+    }
+    Ok(LoadedDevices(instance, entry, messenger, numbered_devices))
+}
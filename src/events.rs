@@ -0,0 +1,213 @@
+use crate::output::{FileLock, NowTime};
+use std::{
+    fmt,
+    fs::File,
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering::SeqCst},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Machine-readable event stream, one JSON object per line. Console and worker processes
+/// append to the same file, so each line is written under the file lock in a single write.
+static EVENTS_FILE: Mutex<Option<File>> = Mutex::new(None);
+static PROCESS_KIND: Mutex<&str> = Mutex::new("console");
+/// Worker sends events to the console process as stdout lines, see [`crate::ipc`]
+static IPC_ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn init(path: &str, process_kind: &'static str) -> std::io::Result<()> {
+    let file = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)?;
+    *EVENTS_FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+    *PROCESS_KIND.lock().unwrap_or_else(|e| e.into_inner()) = process_kind;
+    Ok(())
+}
+
+pub fn init_ipc(process_kind: &'static str) {
+    IPC_ENABLED.store(true, SeqCst);
+    *PROCESS_KIND.lock().unwrap_or_else(|e| e.into_inner()) = process_kind;
+}
+
+/// Sends a short liveness message to the console process, at most once per second. Lets the
+/// console process tell a long-running test from a hung one.
+pub fn heartbeat() {
+    static LAST_HEARTBEAT: Mutex<Option<Instant>> = Mutex::new(None);
+    if !IPC_ENABLED.load(SeqCst) {
+        return;
+    }
+    let mut last = LAST_HEARTBEAT.lock().unwrap_or_else(|e| e.into_inner());
+    if last.map_or(false, |last| last.elapsed() < Duration::from_secs(1)) {
+        return;
+    }
+    *last = Some(Instant::now());
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(
+        stdout,
+        "{}{{\"event\":\"{}\"}}",
+        crate::ipc::MARKER,
+        crate::ipc::HEARTBEAT_EVENT
+    );
+    let _ = stdout.flush();
+}
+
+/// Writes the event line received from another process to the stream if it is enabled.
+pub fn forward(json: &str) {
+    let mut file = EVENTS_FILE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(file) = file.as_mut() {
+        if let Ok(mut locked) = FileLock::wrap_exclusive(file) {
+            let _ = writeln!(locked, "{json}");
+        }
+    }
+}
+
+/// Raw value of the top-level field of an event line produced by [`Event`]: number or array
+/// text, or string contents without unescaping.
+pub fn json_field<'a>(json: &'a str, name: &str) -> Option<&'a str> {
+    let key = format!("\"{name}\":");
+    let mut search_from = 0;
+    let value_start = loop {
+        let key_pos = search_from + json[search_from..].find(&key)?;
+        if key_pos > 0 && matches!(json.as_bytes()[key_pos - 1], b'{' | b',') {
+            break key_pos + key.len();
+        }
+        search_from = key_pos + key.len();
+    };
+    let value = &json[value_start..];
+    if let Some(string) = value.strip_prefix('"') {
+        let mut escaped = false;
+        for (i, c) in string.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => return Some(&string[..i]),
+                _ => {}
+            }
+        }
+        None
+    } else if value.starts_with('[') {
+        value.find(']').map(|end| &value[..=end])
+    } else {
+        value.find([',', '}']).map(|end| &value[..end])
+    }
+}
+
+pub struct Event {
+    json: String,
+}
+
+impl Event {
+    pub fn new(kind: &str) -> Self {
+        let process = *PROCESS_KIND.lock().unwrap_or_else(|e| e.into_inner());
+        Self {
+            json: String::from("{"),
+        }
+        .str("time", &NowTime.to_string())
+        .str("process", process)
+        .num("pid", std::process::id())
+        .str("event", kind)
+    }
+
+    fn key(&mut self, name: &str) {
+        if self.json.len() > 1 {
+            self.json.push(',');
+        }
+        write_json_str(&mut self.json, name);
+        self.json.push(':');
+    }
+
+    pub fn str(mut self, name: &str, value: &str) -> Self {
+        self.key(name);
+        write_json_str(&mut self.json, value);
+        self
+    }
+
+    pub fn num(mut self, name: &str, value: impl fmt::Display) -> Self {
+        self.key(name);
+        let _ = fmt::Write::write_fmt(&mut self.json, format_args!("{value}"));
+        self
+    }
+
+    pub fn float(mut self, name: &str, value: f64) -> Self {
+        self.key(name);
+        if value.is_finite() {
+            let _ = fmt::Write::write_fmt(&mut self.json, format_args!("{value}"));
+        } else {
+            self.json.push_str("null");
+        }
+        self
+    }
+
+    pub fn bool(mut self, name: &str, value: bool) -> Self {
+        self.key(name);
+        self.json.push_str(if value { "true" } else { "false" });
+        self
+    }
+
+    pub fn nums<T: fmt::Display>(
+        mut self,
+        name: &str,
+        values: impl IntoIterator<Item = T>,
+    ) -> Self {
+        self.key(name);
+        self.json.push('[');
+        for (i, value) in values.into_iter().enumerate() {
+            if i > 0 {
+                self.json.push(',');
+            }
+            let _ = fmt::Write::write_fmt(&mut self.json, format_args!("{value}"));
+        }
+        self.json.push(']');
+        self
+    }
+
+    pub fn strs<T: AsRef<str>>(mut self, name: &str, values: impl IntoIterator<Item = T>) -> Self {
+        self.key(name);
+        self.json.push('[');
+        for (i, value) in values.into_iter().enumerate() {
+            if i > 0 {
+                self.json.push(',');
+            }
+            write_json_str(&mut self.json, value.as_ref());
+        }
+        self.json.push(']');
+        self
+    }
+
+    /// Writes the event to the stream if it is enabled.
+    pub fn emit(mut self) {
+        self.json.push_str("}\n");
+        if IPC_ENABLED.load(SeqCst) {
+            let mut stdout = std::io::stdout().lock();
+            let _ = write!(stdout, "{}{}", crate::ipc::MARKER, self.json);
+            let _ = stdout.flush();
+        }
+        let mut file = EVENTS_FILE.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(file) = file.as_mut() {
+            if let Ok(mut locked) = FileLock::wrap_exclusive(file) {
+                let _ = locked.write_all(self.json.as_bytes());
+            }
+        }
+    }
+}
+
+fn write_json_str(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = fmt::Write::write_fmt(out, format_args!("\\u{:04x}", c as u32));
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,259 @@
+use byte_strings::c_str;
+use erupt::{vk, DeviceLoader, InstanceLoader};
+use std::{
+    ffi::{c_void, CStr},
+    fmt, mem,
+    os::raw::c_char,
+    ptr,
+};
+
+//VK_EXT_device_fault is newer than the bindings, so its structures are declared here
+pub const EXT_DEVICE_FAULT_EXTENSION_NAME: &CStr = c_str!("VK_EXT_device_fault");
+const GET_DEVICE_FAULT_INFO_NAME: &CStr = c_str!("vkGetDeviceFaultInfoEXT");
+const STRUCTURE_TYPE_PHYSICAL_DEVICE_FAULT_FEATURES: vk::StructureType =
+    vk::StructureType(1000341000);
+const STRUCTURE_TYPE_DEVICE_FAULT_COUNTS: vk::StructureType = vk::StructureType(1000341001);
+const STRUCTURE_TYPE_DEVICE_FAULT_INFO: vk::StructureType = vk::StructureType(1000341002);
+const DESCRIPTION_SIZE: usize = vk::MAX_DESCRIPTION_SIZE as usize;
+
+#[repr(C)]
+pub struct PhysicalDeviceFaultFeatures {
+    s_type: vk::StructureType,
+    p_next: *mut c_void,
+    pub device_fault: vk::Bool32,
+    pub device_fault_vendor_binary: vk::Bool32,
+}
+
+impl PhysicalDeviceFaultFeatures {
+    /// Features for the query of support or, with `device_fault`, for enabling at device creation
+    pub fn new(device_fault: bool) -> Self {
+        Self {
+            s_type: STRUCTURE_TYPE_PHYSICAL_DEVICE_FAULT_FEATURES,
+            p_next: ptr::null_mut(),
+            device_fault: vk::Bool32::from(device_fault),
+            device_fault_vendor_binary: vk::FALSE,
+        }
+    }
+
+    pub fn as_p_next(&mut self) -> *mut c_void {
+        self as *mut Self as *mut c_void
+    }
+}
+
+#[repr(C)]
+struct DeviceFaultCounts {
+    s_type: vk::StructureType,
+    p_next: *mut c_void,
+    address_info_count: u32,
+    vendor_info_count: u32,
+    vendor_binary_size: vk::DeviceSize,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct DeviceFaultAddressInfo {
+    address_type: i32,
+    reported_address: vk::DeviceAddress,
+    address_precision: vk::DeviceSize,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct DeviceFaultVendorInfo {
+    description: [c_char; DESCRIPTION_SIZE],
+    vendor_fault_code: u64,
+    vendor_fault_data: u64,
+}
+
+#[repr(C)]
+struct DeviceFaultInfo {
+    s_type: vk::StructureType,
+    p_next: *mut c_void,
+    description: [c_char; DESCRIPTION_SIZE],
+    p_address_infos: *mut DeviceFaultAddressInfo,
+    p_vendor_infos: *mut DeviceFaultVendorInfo,
+    p_vendor_binary_data: *mut c_void,
+}
+
+type GetDeviceFaultInfo = unsafe extern "system" fn(
+    vk::Device,
+    *mut DeviceFaultCounts,
+    *mut DeviceFaultInfo,
+) -> vk::Result;
+
+/// Whether the device supports the extension together with its `deviceFault` feature
+pub fn is_supported(instance: &InstanceLoader, physical_device: vk::PhysicalDevice) -> bool {
+    let extensions = match unsafe {
+        instance.enumerate_device_extension_properties(physical_device, None, None)
+    }
+    .result()
+    {
+        Ok(extensions) => extensions,
+        Err(_) => return false,
+    };
+    let has_extension = extensions.iter().any(|ext| {
+        let name = unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) };
+        name == EXT_DEVICE_FAULT_EXTENSION_NAME
+    });
+    if !has_extension {
+        return false;
+    }
+    let mut features = PhysicalDeviceFaultFeatures::new(false);
+    let mut features_request = *vk::PhysicalDeviceFeatures2Builder::new();
+    features_request.p_next = features.as_p_next();
+    unsafe { instance.get_physical_device_features2(physical_device, Some(features_request)) };
+    features.device_fault == vk::TRUE
+}
+
+pub struct FaultAddress {
+    pub kind: &'static str,
+    pub address: u64,
+    pub precision: u64,
+}
+
+impl fmt::Display for FaultAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        //precision is a power of two, the fault is somewhere in the aligned range of that size
+        let mask = self.precision.max(1) - 1;
+        write!(
+            f,
+            "{} 0x{:X}..=0x{:X}",
+            self.kind,
+            self.address & !mask,
+            self.address | mask
+        )
+    }
+}
+
+pub struct VendorFault {
+    pub description: String,
+    pub code: u64,
+    pub data: u64,
+}
+
+impl fmt::Display for VendorFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} code 0x{:X} data 0x{:X}",
+            self.description, self.code, self.data
+        )
+    }
+}
+
+/// Fault records reported by the driver after the device loss
+pub struct FaultReport {
+    pub description: String,
+    pub addresses: Vec<FaultAddress>,
+    pub vendor_faults: Vec<VendorFault>,
+}
+
+impl fmt::Display for FaultReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Device fault: {}", self.description)?;
+        for address in &self.addresses {
+            write!(f, "\n  address {address}")?;
+        }
+        for vendor_fault in &self.vendor_faults {
+            write!(f, "\n  vendor fault {vendor_fault}")?;
+        }
+        Ok(())
+    }
+}
+
+fn address_kind(address_type: i32) -> &'static str {
+    match address_type {
+        1 => "read invalid",
+        2 => "write invalid",
+        3 => "execute invalid",
+        4 => "instruction pointer unknown",
+        5 => "instruction pointer invalid",
+        6 => "instruction pointer fault",
+        _ => "unknown",
+    }
+}
+
+fn c_chars_to_string(chars: &[c_char]) -> String {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .take_while(|c| **c != 0)
+        .map(|c| *c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Reads fault records of the lost device. `enabled` tells if the extension was enabled at the
+/// device creation, otherwise the reason of absent records is returned as an error.
+pub fn query(device: &DeviceLoader, enabled: bool) -> Result<FaultReport, String> {
+    if !enabled {
+        return Err("VK_EXT_device_fault is not supported by the device".to_string());
+    }
+    let get_fault_info =
+        match unsafe { device.get_device_proc_addr(Some(GET_DEVICE_FAULT_INFO_NAME)) } {
+            Some(get_fault_info) => get_fault_info,
+            None => return Err("vkGetDeviceFaultInfoEXT is not available".to_string()),
+        };
+    let get_fault_info: GetDeviceFaultInfo = unsafe { mem::transmute(get_fault_info) };
+    let mut counts = DeviceFaultCounts {
+        s_type: STRUCTURE_TYPE_DEVICE_FAULT_COUNTS,
+        p_next: ptr::null_mut(),
+        address_info_count: 0,
+        vendor_info_count: 0,
+        vendor_binary_size: 0,
+    };
+    let count_result = unsafe { get_fault_info(device.handle, &mut counts, ptr::null_mut()) };
+    if count_result != vk::Result::SUCCESS {
+        return Err(format!("vkGetDeviceFaultInfoEXT counts: {count_result}"));
+    }
+    let mut address_infos = vec![
+        DeviceFaultAddressInfo {
+            address_type: 0,
+            reported_address: 0,
+            address_precision: 0,
+        };
+        counts.address_info_count as usize
+    ];
+    let mut vendor_infos = vec![
+        DeviceFaultVendorInfo {
+            description: [0; DESCRIPTION_SIZE],
+            vendor_fault_code: 0,
+            vendor_fault_data: 0,
+        };
+        counts.vendor_info_count as usize
+    ];
+    //vendor binary isn't interpreted, so it is not requested
+    counts.vendor_binary_size = 0;
+    let mut info = DeviceFaultInfo {
+        s_type: STRUCTURE_TYPE_DEVICE_FAULT_INFO,
+        p_next: ptr::null_mut(),
+        description: [0; DESCRIPTION_SIZE],
+        p_address_infos: address_infos.as_mut_ptr(),
+        p_vendor_infos: vendor_infos.as_mut_ptr(),
+        p_vendor_binary_data: ptr::null_mut(),
+    };
+    let info_result = unsafe { get_fault_info(device.handle, &mut counts, &mut info) };
+    if info_result != vk::Result::SUCCESS && info_result != vk::Result::INCOMPLETE {
+        return Err(format!("vkGetDeviceFaultInfoEXT: {info_result}"));
+    }
+    address_infos.truncate(counts.address_info_count as usize);
+    vendor_infos.truncate(counts.vendor_info_count as usize);
+    Ok(FaultReport {
+        description: c_chars_to_string(&info.description),
+        addresses: address_infos
+            .iter()
+            .map(|info| FaultAddress {
+                kind: address_kind(info.address_type),
+                address: info.reported_address,
+                precision: info.address_precision,
+            })
+            .collect(),
+        vendor_faults: vendor_infos
+            .iter()
+            .map(|info| VendorFault {
+                description: c_chars_to_string(&info.description),
+                code: info.vendor_fault_code,
+                data: info.vendor_fault_data,
+            })
+            .collect(),
+    })
+}
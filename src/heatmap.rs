@@ -0,0 +1,145 @@
+use std::{fmt, io};
+
+/// Count of error counters across the whole tested memory
+pub const HEAT_MAP_BUCKETS: usize = 1024;
+const ROW_BUCKETS: usize = 64;
+/// Characters of the text map from the smallest non-zero count to the biggest one
+const SCALE: &[u8] = b".:-=+*#%@";
+/// Pixels per bucket side in the exported image
+const CELL_PIXELS: usize = 8;
+
+/// Split of the tested memory into equal buckets of whole values. The shader counts errors by
+/// the bucket of the absolute offset: the window's first bucket and the position inside it are
+/// passed as parameters, so the buckets cover all windows regardless of the window size.
+#[derive(Copy, Clone)]
+pub struct BucketLayout {
+    pub bucket_values: i64,
+    pub value_size: i64,
+    pub bucket_count: usize,
+}
+
+impl BucketLayout {
+    pub fn new(total_bytes: i64, value_size: i64) -> Self {
+        let total_values = total_bytes / value_size;
+        let bucket_values = (total_values + HEAT_MAP_BUCKETS as i64 - 1) / HEAT_MAP_BUCKETS as i64;
+        let bucket_values = bucket_values.max(1);
+        Self {
+            bucket_values,
+            value_size,
+            bucket_count: ((total_values + bucket_values - 1) / bucket_values) as usize,
+        }
+    }
+
+    pub fn bucket_bytes(&self) -> i64 {
+        self.bucket_values * self.value_size
+    }
+
+    /// First bucket of the window at the absolute offset and the window start inside it
+    pub fn window_params(&self, window_offset: i64) -> (u32, u32) {
+        let window_value = window_offset / self.value_size;
+        (
+            (window_value / self.bucket_values) as u32,
+            (window_value % self.bucket_values) as u32,
+        )
+    }
+}
+
+/// Errors accumulated by buckets of the absolute offset for the whole run
+pub struct HeatMap {
+    layout: BucketLayout,
+    counts: Vec<u64>,
+}
+
+impl HeatMap {
+    pub fn new(layout: BucketLayout) -> Self {
+        Self {
+            layout,
+            counts: vec![0; layout.bucket_count],
+        }
+    }
+
+    /// Adds counts of a single dispatch
+    pub fn add(&mut self, dispatch_counts: &[u32]) {
+        for (count, added) in self.counts.iter_mut().zip(dispatch_counts) {
+            *count += *added as u64;
+        }
+    }
+
+    pub fn layout(&self) -> BucketLayout {
+        self.layout
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    fn max(&self) -> u64 {
+        self.counts.iter().copied().max().unwrap_or_default()
+    }
+
+    /// Binary PPM image: a cell for each bucket in rows of the text map, black for no errors
+    /// and from dark red to yellow for more errors
+    pub fn write_ppm(&self, out: &mut impl io::Write) -> io::Result<()> {
+        let rows = (self.counts.len() + ROW_BUCKETS - 1) / ROW_BUCKETS;
+        let (width, height) = (ROW_BUCKETS * CELL_PIXELS, rows * CELL_PIXELS);
+        write!(out, "P6\n{width} {height}\n255\n")?;
+        let max = self.max();
+        for y in 0..height {
+            let mut line = Vec::with_capacity(width * 3);
+            for x in 0..width {
+                let bucket = y / CELL_PIXELS * ROW_BUCKETS + x / CELL_PIXELS;
+                let level = self
+                    .counts
+                    .get(bucket)
+                    .map_or(0, |count| level(*count, max));
+                let heat = level as f64 / SCALE.len() as f64;
+                line.extend(match level {
+                    0 => [0, 0, 0],
+                    _ => [(96.0 + 159.0 * heat) as u8, (255.0 * heat * heat) as u8, 0],
+                });
+            }
+            out.write_all(&line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Index of the scale step for the count, 0 for no errors. Scale is logarithmic, so a single hot
+/// spot doesn't hide the rest of errors
+fn level(count: u64, max: u64) -> usize {
+    if count == 0 {
+        return 0;
+    }
+    let share = ((count + 1) as f64).ln() / ((max + 1) as f64).ln();
+    1 + ((share * (SCALE.len() - 1) as f64).round() as usize).min(SCALE.len() - 1)
+}
+
+impl fmt::Display for HeatMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Error heat map: {} errors, each char covers 0x{:X} bytes, scale '{}' up to {}",
+            self.total(),
+            self.layout.bucket_bytes(),
+            String::from_utf8_lossy(SCALE),
+            self.max()
+        )?;
+        let max = self.max();
+        for (row, row_counts) in self.counts.chunks(ROW_BUCKETS).enumerate() {
+            let row_start = (row * ROW_BUCKETS) as i64 * self.layout.bucket_bytes();
+            let row_chars: String = row_counts
+                .iter()
+                .map(|count| match level(*count, max) {
+                    0 => ' ',
+                    level => SCALE[level - 1] as char,
+                })
+                .collect();
+            writeln!(f, "0x{row_start:09X} |{row_chars}|")?;
+        }
+        Ok(())
+    }
+}
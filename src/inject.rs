@@ -0,0 +1,297 @@
+//! Faults injected into the tested data by the shader itself. They produce known error
+//! signatures for validating the error reports and the diagnosis. Debug builds can also fail
+//! Vulkan calls on purpose to exercise the recovery paths, see [`CallFault`].
+
+use crate::engine::{IOBuf, ELEMENT_SIZE, VEC_SIZE};
+use erupt::vk;
+use std::fmt;
+
+//values must match FAULT_* constants in WGSL code
+pub(crate) const FAULT_NONE: u32 = 0;
+pub(crate) const FAULT_BIT_FLIP: u32 = 1;
+pub(crate) const FAULT_STUCK_AT_0: u32 = 2;
+pub(crate) const FAULT_STUCK_AT_1: u32 = 3;
+pub(crate) const FAULT_ALIAS: u32 = 4;
+pub(crate) const FAULT_STORM: u32 = 5;
+pub(crate) const FAULT_ON_WRITE: u32 = 1;
+pub(crate) const FAULT_ON_READ: u32 = 2;
+
+const VEC_BYTES: i64 = VEC_SIZE as i64 * ELEMENT_SIZE;
+/// lanes of the 128-bit vector, numbered as in the lane tables: component * 32 + bit
+pub const LANES: u32 = 128;
+
+/// Injected data fault
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FaultModel {
+    /// flips the bit of the u32 at the byte address of the tested memory
+    BitFlip { address: i64, bit: u32 },
+    /// flips the bit of the u32 at the byte offset inside every test window
+    WindowFlip { offset: i64, bit: u32 },
+    /// forces the lane bit of every vector to the value
+    StuckLane { lane: u32, value: bool },
+    /// accesses of each vector land at its byte offset XOR mask inside the test window; the mask
+    /// is a multiple of the 16-byte vector size
+    Alias { mask: i64 },
+    /// flips a random bit of each u32 with the probability
+    Storm { rate: f64 },
+}
+
+/// Accesses affected by the fault
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FaultPath {
+    /// wrong data is stored by the test writes, the rewrite of the error confirmation is right
+    Write,
+    /// data is stored right, but read wrong
+    Read,
+    /// only reads of data checked before: the re-read window and the confirmation re-reads
+    Reread,
+}
+
+impl FaultPath {
+    pub fn name(self) -> &'static str {
+        match self {
+            FaultPath::Write => "write",
+            FaultPath::Read => "read",
+            FaultPath::Reread => "reread",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [FaultPath::Write, FaultPath::Read, FaultPath::Reread]
+            .into_iter()
+            .find(|path| path.name() == name)
+    }
+}
+
+/// Purpose of a dispatch, it decides which fault paths are active
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Access {
+    /// usual write or check of the test data
+    Test,
+    /// check of data checked before
+    Reread,
+    /// write of the error confirmation, restoring the data in the failing window
+    Rewrite,
+}
+
+/// Fault injected on the path in every iteration or in a single one
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FaultSpec {
+    pub model: FaultModel,
+    pub path: FaultPath,
+    pub iteration: Option<u32>,
+}
+
+impl FaultSpec {
+    /// Fault formerly emulated by the separate shader kernel: bit 22 of y component of the
+    /// vector 0xADBA in every rewritten window is stored wrong on the single iteration
+    pub fn legacy_write_bug(iteration: u32) -> Self {
+        Self {
+            model: FaultModel::WindowFlip {
+                offset: 0xADBA * VEC_BYTES + ELEMENT_SIZE,
+                bit: 22,
+            },
+            path: FaultPath::Write,
+            iteration: Some(iteration),
+        }
+    }
+
+    /// Sets the shader parameters of the fault for the dispatch of the window at `test_offset`
+    pub(crate) fn set_params(
+        &self,
+        io: &mut IOBuf,
+        test_offset: i64,
+        window_size: i64,
+        iteration: u32,
+        access: Access,
+        seed: u32,
+    ) {
+        io.fault_model = FAULT_NONE;
+        if self.iteration.map_or(false, |only| only != iteration) {
+            return;
+        }
+        io.fault_path = match (self.path, access) {
+            (FaultPath::Write, Access::Test) => FAULT_ON_WRITE,
+            (FaultPath::Read, Access::Test | Access::Reread) => FAULT_ON_READ,
+            (FaultPath::Reread, Access::Reread) => FAULT_ON_READ,
+            _ => return,
+        };
+        (io.fault_model, io.fault_addr, io.fault_bit, io.fault_rate) = match self.model {
+            FaultModel::BitFlip { address, bit } => {
+                if !(test_offset..test_offset + window_size).contains(&address) {
+                    return;
+                }
+                let index = (address - test_offset) / ELEMENT_SIZE;
+                (FAULT_BIT_FLIP, index as u32, bit, 0)
+            }
+            FaultModel::WindowFlip { offset, bit } => {
+                if offset >= window_size {
+                    return;
+                }
+                (FAULT_BIT_FLIP, (offset / ELEMENT_SIZE) as u32, bit, 0)
+            }
+            FaultModel::StuckLane { lane, value: false } => (FAULT_STUCK_AT_0, 0, lane, 0),
+            FaultModel::StuckLane { lane, value: true } => (FAULT_STUCK_AT_1, 0, lane, 0),
+            //masks over u32 vectors alias outside of any window, as the saturated one does
+            FaultModel::Alias { mask } => {
+                let vectors = u32::try_from(mask / VEC_BYTES).unwrap_or(u32::MAX);
+                (FAULT_ALIAS, vectors, 0, 0)
+            }
+            FaultModel::Storm { rate } => (FAULT_STORM, 0, 0, (rate * 2f64.powi(32)) as u32),
+        };
+        io.fault_seed = seed;
+    }
+}
+
+/// Formats the fault in the same syntax as accepted by `--inject`
+impl fmt::Display for FaultSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.model {
+            FaultModel::BitFlip { address, bit } => write!(f, "flip:0x{address:X}:{bit}")?,
+            FaultModel::WindowFlip { offset, bit } => write!(f, "window-flip:0x{offset:X}:{bit}")?,
+            FaultModel::StuckLane { lane, value } => {
+                write!(f, "stuck-at-{}:{lane}", u8::from(value))?
+            }
+            FaultModel::Alias { mask } => write!(f, "alias:0x{mask:X}")?,
+            FaultModel::Storm { rate } => write!(f, "storm:{rate}")?,
+        }
+        write!(f, "/{}", self.path.name())?;
+        if let Some(iteration) = self.iteration {
+            write!(f, "@{iteration}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Vulkan calls of the backend that can be failed by [`CallFault`]
+const FAILABLE_CALLS: [&str; 6] = [
+    "create_buffer",
+    "allocate_memory",
+    "bind_buffer_memory",
+    "queue_submit",
+    "wait_for_fences",
+    "reset_fences",
+];
+
+/// Results accepted by name in [`CallFault`], other ones can be given as numbers
+const NAMED_RESULTS: [vk::Result; 8] = [
+    vk::Result::TIMEOUT,
+    vk::Result::ERROR_OUT_OF_HOST_MEMORY,
+    vk::Result::ERROR_OUT_OF_DEVICE_MEMORY,
+    vk::Result::ERROR_INITIALIZATION_FAILED,
+    vk::Result::ERROR_DEVICE_LOST,
+    vk::Result::ERROR_MEMORY_MAP_FAILED,
+    vk::Result::ERROR_TOO_MANY_OBJECTS,
+    vk::Result::ERROR_UNKNOWN,
+];
+
+/// Failure of the orchestration injected by debug builds from `MEMTEST_VULKAN_INJECT_CALL`
+/// variable. It fires once per process, on the first matching call of the iteration; calls
+/// before the first iteration, like the test allocations, belong to iteration 0.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CallFault {
+    /// the call returns the result without calling the driver: `allocate_memory:ERROR_OUT_OF_DEVICE_MEMORY@0`
+    Fail {
+        call: &'static str,
+        result: vk::Result,
+        iteration: Option<u32>,
+    },
+    /// the dispatch isn't submitted, so its fence is never signalled: `hang@2`
+    Hang { iteration: Option<u32> },
+}
+
+impl CallFault {
+    pub const ENV_VAR: &'static str = "MEMTEST_VULKAN_INJECT_CALL";
+
+    /// Fault requested by the environment, always None in release builds
+    pub fn from_env() -> Result<Option<Self>, String> {
+        if !cfg!(debug_assertions) {
+            return Ok(None);
+        }
+        match std::env::var(Self::ENV_VAR) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Parses `CALL:RESULT[@ITERATION]` or `hang[@ITERATION]`, the result is a Vulkan name
+    /// like `ERROR_DEVICE_LOST` or a number
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = |reason: &str| format!("invalid call fault '{text}': {reason}");
+        let (rest, iteration) = match text.trim().rsplit_once('@') {
+            Some((rest, iteration)) => match iteration.parse::<u32>() {
+                Ok(iteration) => (rest, Some(iteration)),
+                _ => return Err(invalid("iteration must be a number")),
+            },
+            None => (text.trim(), None),
+        };
+        if rest == "hang" {
+            return Ok(CallFault::Hang { iteration });
+        }
+        let (call, result) = match rest.split_once(':') {
+            Some(parts) => parts,
+            None => return Err(invalid("expected CALL:RESULT or hang")),
+        };
+        let call = match FAILABLE_CALLS.into_iter().find(|name| *name == call) {
+            Some(call) => call,
+            None => {
+                return Err(invalid(&format!(
+                    "call must be one of {}",
+                    FAILABLE_CALLS.join(", ")
+                )))
+            }
+        };
+        let result_name = result.strip_prefix("VK_").unwrap_or(result);
+        let result = match NAMED_RESULTS
+            .into_iter()
+            .find(|named| format!("{named:?}") == result_name)
+        {
+            Some(result) => result,
+            None => match result.parse::<i32>() {
+                Ok(raw) if raw != 0 => vk::Result(raw),
+                _ => {
+                    return Err(invalid(
+                        "result must be a Vulkan error name or a non-zero number",
+                    ))
+                }
+            },
+        };
+        Ok(CallFault::Fail {
+            call,
+            result,
+            iteration,
+        })
+    }
+
+    fn iteration(&self) -> Option<u32> {
+        match self {
+            CallFault::Fail { iteration, .. } | CallFault::Hang { iteration } => *iteration,
+        }
+    }
+
+    /// Whether the fault fires on the call at the iteration, `hang` matches `queue_submit`
+    pub(crate) fn matches(&self, call: &str, iteration: u32) -> bool {
+        let call_matches = match self {
+            CallFault::Fail { call: failed, .. } => *failed == call,
+            CallFault::Hang { .. } => call == "queue_submit",
+        };
+        call_matches && self.iteration().map_or(true, |only| only == iteration)
+    }
+}
+
+/// Formats the fault in the same syntax as accepted by [`CallFault::parse`]
+impl fmt::Display for CallFault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallFault::Fail { call, result, .. } if NAMED_RESULTS.contains(result) => {
+                write!(f, "{call}:{result:?}")?
+            }
+            CallFault::Fail { call, result, .. } => write!(f, "{call}:{}", result.0)?,
+            CallFault::Hang { .. } => write!(f, "hang")?,
+        }
+        if let Some(iteration) = self.iteration() {
+            write!(f, "@{iteration}")?;
+        }
+        Ok(())
+    }
+}
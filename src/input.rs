@@ -67,7 +67,7 @@ impl Reader {
         }
     }
     pub fn set_pass_fail_accent_color(&mut self, failed: bool) {
-        if crate::close::close_requested() {
+        if memtest_vulkan::close::close_requested() {
             //color methods not available while closing on windows
             return;
         }
@@ -84,7 +84,7 @@ impl Reader {
     }
 
     pub fn wait_any_key(&mut self) {
-        if crate::close::close_requested() {
+        if memtest_vulkan::close::close_requested() {
             //interaction methods not available while closing on windows
             let seconds_wait = 3;
             let mut out = std::io::stdout();
@@ -173,7 +173,7 @@ impl Reader {
 
 impl Drop for Reader {
     fn drop(&mut self) {
-        if crate::close::close_requested() {
+        if memtest_vulkan::close::close_requested() {
             return; //don't touch console methods while closing
         }
         if let Some(terminal) = &mut self.terminal {
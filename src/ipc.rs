@@ -0,0 +1,90 @@
+use crate::events::json_field;
+
+/// Worker sends to the console process the same JSON objects as written to the events file,
+/// each one as a separate stdout line starting with the marker. Other stdout lines are usual
+/// human-readable output.
+pub const MARKER: &str = "\u{1e}memtest_vulkan_event:";
+/// Liveness message, not saved to the events file
+pub const HEARTBEAT_EVENT: &str = "heartbeat";
+
+/// Worker state known by the console process from the worker messages. Kept after the worker
+/// exit, so even a crashed worker leaves its last known state behind.
+#[derive(Default)]
+pub struct WorkerState {
+    pub messages: u64,
+    pub last_event: Option<String>,
+    pub last_event_time: Option<String>,
+    pub tested_bytes: Option<i64>,
+    pub iteration: Option<i64>,
+    pub error_blocks: u64,
+    pub total_errors: i64,
+    /// final status sent by the worker just before exit
+    pub summary_status: Option<u8>,
+    pub summary_message: Option<String>,
+    pub gpu_hang: Option<String>,
+    pub device_lost: Option<String>,
+}
+
+impl WorkerState {
+    /// Splits the output line into the human-readable output preceding the message and the
+    /// message JSON object, updating the state by the message. Usually the preceding part is
+    /// empty, but the message can be emitted while a human-readable line is not finished yet.
+    pub fn take_message<'a>(&mut self, line: &'a str) -> Option<(&'a str, &'a str)> {
+        let (preceding, json) = line.split_once(MARKER)?;
+        let json = json.trim_end();
+        let number = |name| json_field(json, name).and_then(|value| value.parse().ok());
+        if json_field(json, "event") == Some(HEARTBEAT_EVENT) {
+            return Some((preceding, json));
+        }
+        self.messages += 1;
+        match json_field(json, "event") {
+            Some("allocation") => self.tested_bytes = number("bytes"),
+            Some("report" | "stopped") => self.iteration = number("iteration").or(self.iteration),
+            Some("error") => {
+                self.error_blocks += 1;
+                self.total_errors += number("total_errors").unwrap_or_default();
+            }
+            Some("gpu_hang") => self.gpu_hang = json_field(json, "description").map(str::to_string),
+            Some("device_lost") => {
+                self.device_lost = json_field(json, "description").map(str::to_string);
+            }
+            Some("summary") => {
+                self.summary_status = number("status").and_then(|status| u8::try_from(status).ok());
+                self.summary_message = json_field(json, "message").map(str::to_string);
+            }
+            _ => {}
+        }
+        self.last_event = json_field(json, "event").map(str::to_string);
+        self.last_event_time = json_field(json, "time").map(str::to_string);
+        Some((preceding, json))
+    }
+
+    pub fn describe(&self) -> String {
+        let last_event = match &self.last_event {
+            Some(last_event) => last_event,
+            None => return "no messages received".to_string(),
+        };
+        let mut description = format!(
+            "{} messages, last '{}' at {}",
+            self.messages,
+            last_event,
+            self.last_event_time.as_deref().unwrap_or("unknown time")
+        );
+        if let Some(tested_bytes) = self.tested_bytes {
+            description += &format!(", testing {tested_bytes} bytes");
+        }
+        if let Some(iteration) = self.iteration {
+            description += &format!(", reached iteration {iteration}");
+        }
+        if self.error_blocks > 0 {
+            description += &format!(
+                ", {} error reports with 0x{:X} errors",
+                self.error_blocks, self.total_errors
+            );
+        }
+        if let Some(message) = &self.summary_message {
+            description += &format!(", final message: {message}");
+        }
+        description
+    }
+}
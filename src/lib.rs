@@ -0,0 +1,39 @@
+//! Core of the memtest_vulkan GPU memory tester. The command line tool is built on the same API:
+//! devices are listed with [`enumerate_devices`], a device is tested by a [`TestSession`] and the
+//! progress is received by a [`TestObserver`].
+
+mod backend;
+pub mod diagnosis;
+mod engine;
+pub mod fault;
+pub mod heatmap;
+pub mod inject;
+pub mod modes;
+pub mod pattern;
+pub mod schedule;
+mod session;
+pub mod simulated;
+mod vulkan;
+
+//used by the command line tool, not a stable API
+#[doc(hidden)]
+pub mod allocation;
+#[doc(hidden)]
+pub mod close;
+#[doc(hidden)]
+pub mod events;
+#[doc(hidden)]
+pub mod ipc;
+#[doc(hidden)]
+pub mod output;
+
+pub use engine::{
+    list_devices_ordered_labaled_from_1, test_loaded_device, try_fill_default_mem_budget,
+    DeviceLost, GpuHang, LoadedDevices, NamedComputeDevice, MIN_WANTED_ALLOCATION,
+};
+#[doc(hidden)]
+pub use engine::{AllocationFailed, ProbeSucceeded};
+pub use session::{
+    enumerate_devices, request_stop, ErrorBlock, Outcome, Progress, TestConfig, TestObserver,
+    TestSession, TestSessionBuilder, Verdict, DEFAULT_ALLOC_PRECISION,
+};
@@ -0,0 +1,133 @@
+use std::fmt;
+
+/// Overall testing algorithm executed on each iteration
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum TestMode {
+    /// Write all windows except the first one, then check all windows. The first window is
+    /// written once at start and re-read on every iteration
+    #[default]
+    Standard,
+    /// March C- element sequence over every window
+    MarchC,
+    /// Fill all windows, keep gpu idle for a hold time, verify; then the same with inverted data
+    Retention,
+}
+
+impl TestMode {
+    pub const ALL: [TestMode; 3] = [TestMode::Standard, TestMode::MarchC, TestMode::Retention];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TestMode::Standard => "standard",
+            TestMode::MarchC => "march-c",
+            TestMode::Retention => "retention",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+impl fmt::Display for TestMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// march operation bits, must match MARCH_* constants in WGSL code
+pub const MARCH_READ_BACKGROUND: u32 = 1;
+pub const MARCH_READ_INVERTED: u32 = 2;
+pub const MARCH_WRITE_BACKGROUND: u32 = 4;
+pub const MARCH_WRITE_INVERTED: u32 = 8;
+pub const MARCH_DESCENDING: u32 = 16;
+
+/// Single march element: read-verify-write operation applied to every element of a window.
+/// "0" is the data background given by the selected pattern, "1" is its inversion.
+pub struct MarchElement {
+    pub name: &'static str,
+    pub op: u32,
+}
+
+impl MarchElement {
+    pub fn reads(&self) -> bool {
+        self.op & (MARCH_READ_BACKGROUND | MARCH_READ_INVERTED) != 0
+    }
+    pub fn writes(&self) -> bool {
+        self.op & (MARCH_WRITE_BACKGROUND | MARCH_WRITE_INVERTED) != 0
+    }
+    pub fn descending(&self) -> bool {
+        self.op & MARCH_DESCENDING != 0
+    }
+    /// Read operation verifying the data left in the window by the element
+    pub fn check_op(&self) -> u32 {
+        if self.op & MARCH_WRITE_INVERTED != 0 {
+            MARCH_READ_INVERTED
+        } else if self.op & MARCH_WRITE_BACKGROUND != 0 {
+            MARCH_READ_BACKGROUND
+        } else {
+            self.op & (MARCH_READ_BACKGROUND | MARCH_READ_INVERTED)
+        }
+    }
+    /// Write operation restoring the data left in the window by the element
+    pub fn rewrite_op(&self) -> u32 {
+        if self.check_op() == MARCH_READ_INVERTED {
+            MARCH_WRITE_INVERTED
+        } else {
+            MARCH_WRITE_BACKGROUND
+        }
+    }
+}
+
+/// GPU executes invocations of a dispatch in parallel, so the address order inside a window is
+/// only approximately ascending or descending, while windows are processed strictly in order.
+pub const MARCH_C_MINUS: [MarchElement; 6] = [
+    MarchElement {
+        name: "M0 any(w0)",
+        op: MARCH_WRITE_BACKGROUND,
+    },
+    MarchElement {
+        name: "M1 up(r0,w1)",
+        op: MARCH_READ_BACKGROUND | MARCH_WRITE_INVERTED,
+    },
+    MarchElement {
+        name: "M2 up(r1,w0)",
+        op: MARCH_READ_INVERTED | MARCH_WRITE_BACKGROUND,
+    },
+    MarchElement {
+        name: "M3 down(r0,w1)",
+        op: MARCH_READ_BACKGROUND | MARCH_WRITE_INVERTED | MARCH_DESCENDING,
+    },
+    MarchElement {
+        name: "M4 down(r1,w0)",
+        op: MARCH_READ_INVERTED | MARCH_WRITE_BACKGROUND | MARCH_DESCENDING,
+    },
+    MarchElement {
+        name: "M5 any(r0)",
+        op: MARCH_READ_BACKGROUND,
+    },
+];
+
+/// Fill and verify elements of data retention test, the hold time is between them
+pub const RETENTION_PHASES: [(MarchElement, MarchElement); 2] = [
+    (
+        MarchElement {
+            name: "fill(w0)",
+            op: MARCH_WRITE_BACKGROUND,
+        },
+        MarchElement {
+            name: "verify(r0)",
+            op: MARCH_READ_BACKGROUND,
+        },
+    ),
+    (
+        MarchElement {
+            name: "fill(w1)",
+            op: MARCH_WRITE_INVERTED,
+        },
+        MarchElement {
+            name: "verify(r1)",
+            op: MARCH_READ_INVERTED,
+        },
+    ),
+];
@@ -5,6 +5,7 @@ use std::{
     fs::File,
     io,
     io::{Read, Seek, SeekFrom, Write},
+    sync::mpsc,
 };
 
 /// Wrapper over a file that calls [`FileExt::unlock`] at [dropping][`Drop`].
@@ -33,7 +34,7 @@ impl<'a> core::ops::DerefMut for FileLock<'a> {
 
 impl<'a> Drop for FileLock<'a> {
     fn drop(&mut self) {
-        let _ = self.0.unlock();
+        let _ = FileExt::unlock(self.0);
     }
 }
 
@@ -175,3 +176,51 @@ impl<Writer: io::Write> Write for LogDupler<Writer> {
         Ok(())
     }
 }
+
+/// Writer sending each complete line with a prefix to a channel, lets several threads share
+/// one output without mixing parts of lines.
+pub struct PrefixedLines {
+    pub prefix: String,
+    pub sender: mpsc::Sender<String>,
+    pub pending: Vec<u8>,
+}
+
+impl PrefixedLines {
+    pub fn new(prefix: String, sender: mpsc::Sender<String>) -> Self {
+        Self {
+            prefix,
+            sender,
+            pending: Vec::new(),
+        }
+    }
+    fn send_line(&mut self, line: &[u8]) {
+        let _ = self.sender.send(format!(
+            "{}{}\n",
+            self.prefix,
+            String::from_utf8_lossy(line).trim_end_matches('\r')
+        ));
+    }
+}
+
+impl Drop for PrefixedLines {
+    fn drop(&mut self) {
+        if !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            self.send_line(&pending);
+        }
+    }
+}
+
+impl Write for PrefixedLines {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        while let Some(position) = self.pending.iter().position(|c| c == &b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=position).collect();
+            self.send_line(&line[..position]);
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use std::fmt;
+
+/// Data pattern written and checked by shader. Discriminants are passed to shader via
+/// `IOBuf::pattern` and must match `PATTERN_*` constants in WGSL code.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(u32)]
+pub enum TestPattern {
+    Address = 0,
+    Zeros = 1,
+    Ones = 2,
+    Checkerboard = 3,
+    WalkingOnes = 4,
+    WalkingZeros = 5,
+    InvertedAddress = 6,
+}
+
+impl TestPattern {
+    pub const ALL: [TestPattern; 7] = [
+        TestPattern::Address,
+        TestPattern::Zeros,
+        TestPattern::Ones,
+        TestPattern::Checkerboard,
+        TestPattern::WalkingOnes,
+        TestPattern::WalkingZeros,
+        TestPattern::InvertedAddress,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TestPattern::Address => "address",
+            TestPattern::Zeros => "zeros",
+            TestPattern::Ones => "ones",
+            TestPattern::Checkerboard => "checkerboard",
+            TestPattern::WalkingOnes => "walking-ones",
+            TestPattern::WalkingZeros => "walking-zeros",
+            TestPattern::InvertedAddress => "inverted-address",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|pattern| pattern.name() == name)
+    }
+
+    pub fn from_index(index: u32) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|pattern| *pattern as u32 == index)
+    }
+
+    /// CPU reference of `test_value_by_index` shader function for the single u32 with given
+    /// address value
+    pub fn value(self, addr: u32) -> u32 {
+        let rotated = addr.rotate_left(addr % 31u32);
+        match self {
+            TestPattern::Address => rotated,
+            TestPattern::Zeros => 0,
+            TestPattern::Ones => 0xFFFFFFFF,
+            TestPattern::Checkerboard => match addr & 1 {
+                0 => 0x55555555,
+                _ => 0xAAAAAAAA,
+            },
+            TestPattern::WalkingOnes => 1 << (addr % 32),
+            TestPattern::WalkingZeros => !(1 << (addr % 32)),
+            TestPattern::InvertedAddress => !rotated,
+        }
+    }
+
+    pub fn names_list() -> String {
+        Self::ALL.map(TestPattern::name).join(", ")
+    }
+}
+
+impl fmt::Display for TestPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
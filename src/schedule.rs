@@ -0,0 +1,66 @@
+use std::{fmt, time::Duration};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PhaseKind {
+    /// Usual write+check iterations, the phase lasts for whole iterations until its time is over
+    Load,
+    /// No gpu work at all, lets the driver lower memory and core frequencies
+    Idle,
+    /// Single read dispatch of the re-read window, shows errors appeared in the previous phase
+    Check,
+}
+
+/// Single step of the load-cycling schedule; steps are executed cyclically
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LoadPhase {
+    pub kind: PhaseKind,
+    pub duration: Duration,
+}
+
+impl LoadPhase {
+    pub fn name(self) -> &'static str {
+        match self.kind {
+            PhaseKind::Load => "load",
+            PhaseKind::Idle => "idle",
+            PhaseKind::Check => "check",
+        }
+    }
+}
+
+/// Formats the phase in the same syntax as accepted by `--schedule`
+impl fmt::Display for LoadPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            PhaseKind::Check => write!(f, "{}", self.name()),
+            _ => write!(f, "{}:{}ms", self.name(), self.duration.as_millis()),
+        }
+    }
+}
+
+/// Position inside the cyclic schedule
+pub struct ScheduleCursor<'a> {
+    phases: &'a [LoadPhase],
+    index: usize,
+}
+
+impl<'a> ScheduleCursor<'a> {
+    pub fn new(phases: &'a [LoadPhase]) -> Self {
+        Self { phases, index: 0 }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn current(&self) -> LoadPhase {
+        self.phases[self.index]
+    }
+
+    pub fn previous(&self) -> LoadPhase {
+        self.phases[(self.index + self.phases.len() - 1) % self.phases.len()]
+    }
+
+    pub fn advance(&mut self) {
+        self.index = (self.index + 1) % self.phases.len();
+    }
+}
@@ -0,0 +1,359 @@
+use crate::{
+    close, diagnosis,
+    engine::{self, DeviceLost, GpuHang, MIN_WANTED_ALLOCATION},
+    inject,
+    modes::TestMode,
+    output,
+    pattern::TestPattern,
+    schedule,
+    simulated::{SimulatedBackend, SimulatedDevice},
+};
+use std::{io, time};
+
+pub const DEFAULT_ALLOC_PRECISION: i64 = 256 * 1024 * 1024;
+
+/// Parameters of testing a single device
+#[derive(Clone)]
+pub struct TestConfig {
+    /// device number from 1 as in the labels, None or 0 select the first device
+    pub device_label: Option<usize>,
+    /// 0 tests all available memory
+    pub max_test_bytes: i64,
+    /// allocation is retried with sizes differing by this step when the wanted size fails
+    pub alloc_precision: i64,
+    pub max_duration: Option<time::Duration>,
+    pub max_iterations: Option<i32>,
+    /// stop after the standard 5-minute test instead of continuing endlessly
+    pub standard_only: bool,
+    /// patterns are used in turn, one per iteration
+    pub patterns: Vec<TestPattern>,
+    pub mode: TestMode,
+    pub hold_duration: time::Duration,
+    pub hang_timeout: time::Duration,
+    pub confirm_reads: u32,
+    pub schedule: Vec<schedule::LoadPhase>,
+    /// 1 enables verbose output, 2 also passes verbose vulkan validation messages
+    pub verbosity: u8,
+    pub heat_map_file: Option<String>,
+    /// data fault injected by the shader
+    pub inject: Option<inject::FaultSpec>,
+    /// set for the worker process of the command line tool, fails with
+    /// [`crate::AllocationFailed`] on failures during initialization, so the console process
+    /// retries with other memory size
+    #[doc(hidden)]
+    pub worker: bool,
+    /// set for the worker process which only checks the allocation, fails with
+    /// [`crate::ProbeSucceeded`] once it's done
+    #[doc(hidden)]
+    pub probe: bool,
+}
+
+impl Default for TestConfig {
+    fn default() -> Self {
+        Self {
+            device_label: None,
+            max_test_bytes: 0,
+            alloc_precision: DEFAULT_ALLOC_PRECISION,
+            max_duration: None,
+            max_iterations: None,
+            standard_only: false,
+            patterns: vec![TestPattern::Address],
+            mode: TestMode::default(),
+            hold_duration: time::Duration::from_secs(60),
+            hang_timeout: time::Duration::from_secs(30),
+            confirm_reads: 3,
+            schedule: Vec::new(),
+            verbosity: 0,
+            heat_map_file: None,
+            inject: None,
+            worker: false,
+            probe: false,
+        }
+    }
+}
+
+impl TestConfig {
+    pub fn effective_index(&self) -> usize {
+        match self.device_label {
+            None => 0,
+            Some(0) => 0,
+            Some(positive) => positive - 1,
+        }
+    }
+    pub fn verbose(&self) -> bool {
+        self.verbosity >= 1
+    }
+    pub fn duration_exceeded(&self, test_start: time::Instant) -> bool {
+        self.max_duration
+            .map_or(false, |max_duration| test_start.elapsed() >= max_duration)
+    }
+    pub fn pattern_for_iteration(&self, iteration: i32) -> TestPattern {
+        self.patterns[(iteration - 1) as usize % self.patterns.len()]
+    }
+}
+
+/// Testing speed since the previous report, same as printed in the iteration lines
+pub struct Progress {
+    pub iteration: i32,
+    pub elapsed: time::Duration,
+    pub written_bytes: i64,
+    pub write_speed_gbps: f32,
+    pub checked_bytes: i64,
+    pub check_speed_gbps: f32,
+}
+
+/// Errors found by a single check of a test window
+pub struct ErrorBlock<'a> {
+    pub mode: &'a str,
+    pub iteration: u32,
+    pub pattern: TestPattern,
+    pub address_first: i64,
+    pub address_last: i64,
+    pub total_errors: i64,
+    pub tested_elements: i64,
+    pub allocation: usize,
+    /// possible causes, most likely first
+    pub diagnosis: &'a [diagnosis::Finding],
+}
+
+/// Final outcome of testing
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Verdict {
+    Passed,
+    ErrorsFound,
+    /// runtime error after the first test passed
+    Aborted,
+    /// runtime error during initialization or the first test
+    InitFailed,
+    GpuHang,
+    DeviceLost,
+}
+
+impl Verdict {
+    /// Verdict by the status bits of [`close::app_status`]
+    pub fn from_status(status: u8) -> Self {
+        let has_errors = close::check_any_bits_set(status, close::app_status::RUNTIME_ERRORS);
+        if !close::check_any_bits_set(status, close::app_status::INITED_OK) {
+            Verdict::InitFailed
+        } else if close::check_any_bits_set(status, close::app_status::RUNTIME_ABORT) && !has_errors
+        {
+            Verdict::Aborted
+        } else if has_errors {
+            Verdict::ErrorsFound
+        } else {
+            Verdict::Passed
+        }
+    }
+
+    /// Name used in the events
+    pub fn name(self) -> &'static str {
+        match self {
+            Verdict::Passed => "passed",
+            Verdict::ErrorsFound => "errors_found",
+            Verdict::Aborted => "aborted",
+            Verdict::InitFailed => "init_failed",
+            Verdict::GpuHang => "gpu_hang",
+            Verdict::DeviceLost => "device_lost",
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            Verdict::Passed => "no any errors, testing PASSed.",
+            Verdict::ErrorsFound => "memory/gpu ERRORS FOUND, testing finished.",
+            Verdict::Aborted => "First test passed, but THEN runtime error occured",
+            Verdict::InitFailed => "INIT OR FIRST testing failed due to runtime error",
+            Verdict::GpuHang => "GPU HANG detected, testing aborted.",
+            Verdict::DeviceLost => "DEVICE LOST during testing, the device isn't stable.",
+        }
+    }
+}
+
+/// Receiver of the testing events. All methods do nothing by default.
+pub trait TestObserver {
+    /// Called periodically after an iteration, first time after the first iteration
+    fn on_progress(&mut self, _progress: &Progress) {}
+    /// Called for each test window check which found errors
+    fn on_error(&mut self, _error: &ErrorBlock) {}
+    /// Called once when the testing is finished
+    fn on_verdict(&mut self, _verdict: Verdict) {}
+}
+
+impl TestObserver for () {}
+
+/// Result of [`TestSession::run`]: the verdict and the runtime error which stopped testing, if any
+pub struct Outcome {
+    pub verdict: Verdict,
+    pub error: Option<Box<dyn std::error::Error>>,
+}
+
+/// Testing of a single device in this process. The testing state like the stop request is
+/// process-wide, so sessions should be run one at a time.
+///
+/// ```no_run
+/// let session = memtest_vulkan::TestSession::builder()
+///     .device(1)
+///     .duration(std::time::Duration::from_secs(60))
+///     .build()
+///     .unwrap();
+/// let outcome = session.run(std::io::stdout(), &mut ());
+/// println!("{}", outcome.verdict.message());
+/// ```
+pub struct TestSession {
+    config: TestConfig,
+}
+
+impl TestSession {
+    pub fn builder() -> TestSessionBuilder {
+        TestSessionBuilder::default()
+    }
+
+    pub fn config(&self) -> &TestConfig {
+        &self.config
+    }
+
+    /// Tests the device, writing the human-readable output to `out`
+    pub fn run(&self, out: impl io::Write, observer: &mut dyn TestObserver) -> Outcome {
+        close::reset_status();
+        let mut log_dupler = output::LogDupler::new(out, None, 0, "Tester library");
+        let mut config = self.config.clone();
+        let result = engine::list_devices_ordered_labaled_from_1(config.verbosity, &mut log_dupler)
+            .and_then(|loaded_devices| {
+                engine::try_fill_default_mem_budget(&loaded_devices, &mut config, &mut log_dupler);
+                engine::test_loaded_device(loaded_devices, &config, &mut log_dupler, observer)
+            });
+        Self::finish(result, observer)
+    }
+
+    /// Tests the device simulated on the CPU instead of a Vulkan device, all available simulated
+    /// memory is tested by default
+    pub fn run_simulated(
+        &self,
+        device: &SimulatedDevice,
+        out: impl io::Write,
+        observer: &mut dyn TestObserver,
+    ) -> Outcome {
+        close::reset_status();
+        let mut log_dupler = output::LogDupler::new(out, None, 0, "Tester library");
+        let mut config = self.config.clone();
+        if config.max_test_bytes == 0 {
+            config.max_test_bytes = device.memory_size();
+        }
+        let backend = SimulatedBackend::new(device, config.hang_timeout);
+        let result = engine::test_device(
+            &backend,
+            &mut log_dupler,
+            "simulated device",
+            &config,
+            observer,
+        );
+        Self::finish(result, observer)
+    }
+
+    fn finish(
+        result: Result<(), Box<dyn std::error::Error>>,
+        observer: &mut dyn TestObserver,
+    ) -> Outcome {
+        let verdict = match &result {
+            Err(e) if e.is::<GpuHang>() => Verdict::GpuHang,
+            Err(e) if e.is::<DeviceLost>() => Verdict::DeviceLost,
+            Err(_) => {
+                close::raise_status_bit(close::app_status::RUNTIME_ABORT);
+                Verdict::from_status(close::fetch_status())
+            }
+            Ok(()) => Verdict::from_status(close::fetch_status()),
+        };
+        observer.on_verdict(verdict);
+        Outcome {
+            verdict,
+            error: result.err(),
+        }
+    }
+}
+
+/// Builder of [`TestSession`], unset parameters have the defaults of the command line tool
+#[derive(Default)]
+pub struct TestSessionBuilder {
+    config: TestConfig,
+}
+
+impl TestSessionBuilder {
+    /// Device number from 1 as in the labels of [`enumerate_devices`]
+    pub fn device(mut self, label: usize) -> Self {
+        self.config.device_label = Some(label);
+        self
+    }
+
+    /// Tested memory size in bytes, by default all available memory is tested
+    pub fn memory(mut self, bytes: i64) -> Self {
+        self.config.max_test_bytes = bytes;
+        self
+    }
+
+    pub fn patterns(mut self, patterns: Vec<TestPattern>) -> Self {
+        self.config.patterns = patterns;
+        self
+    }
+
+    pub fn mode(mut self, mode: TestMode) -> Self {
+        self.config.mode = mode;
+        self
+    }
+
+    pub fn duration(mut self, duration: time::Duration) -> Self {
+        self.config.max_duration = Some(duration);
+        self
+    }
+
+    pub fn iterations(mut self, iterations: i32) -> Self {
+        self.config.max_iterations = Some(iterations);
+        self
+    }
+
+    pub fn verbosity(mut self, verbosity: u8) -> Self {
+        self.config.verbosity = verbosity;
+        self
+    }
+
+    /// Full control over the parameters not covered by other methods
+    pub fn config(mut self, config: TestConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn build(self) -> Result<TestSession, String> {
+        let config = self.config;
+        if config.max_test_bytes != 0 && config.max_test_bytes < MIN_WANTED_ALLOCATION {
+            return Err(format!(
+                "memory size {} is smaller than minimum of {MIN_WANTED_ALLOCATION} bytes",
+                config.max_test_bytes
+            ));
+        }
+        if config.patterns.is_empty() {
+            return Err("no test patterns given".to_string());
+        }
+        if config
+            .max_iterations
+            .map_or(false, |iterations| iterations < 1)
+        {
+            return Err("iteration count must be positive".to_string());
+        }
+        Ok(TestSession { config })
+    }
+}
+
+/// Labels of compute-capable devices in the order used for selection, numbered from 1
+pub fn enumerate_devices() -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut log_dupler = output::LogDupler::new(io::sink(), None, 0, "Tester library");
+    let loaded_devices = engine::list_devices_ordered_labaled_from_1(0, &mut log_dupler)?;
+    Ok(loaded_devices
+        .devices()
+        .iter()
+        .map(|device| device.label.clone())
+        .collect())
+}
+
+/// Asks the running session to stop after the current iteration, as Ctrl+C does
+pub fn request_stop() {
+    close::request_interrupt();
+}
@@ -0,0 +1,542 @@
+//! Device simulated on the CPU for testing the tester itself: the shader kernels are run in Rust
+//! over sparse memory, and allocation failures, device loss and bit flips can be injected.
+//! The data faults of [`crate::inject`] are applied as by the shader.
+
+use crate::{
+    allocation::AllocationLayout,
+    backend::{AllocationError, Backend, Kernel},
+    engine::{DeviceLost, GpuHang, IOBuf, ELEMENT_BIT_SIZE, ELEMENT_SIZE, ERR_CAPTURE_SIZE},
+    heatmap, inject, modes,
+    pattern::TestPattern,
+};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, BTreeSet, HashMap},
+    time,
+};
+
+/// How an injected bit flip shows up
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum FlipKind {
+    /// the next `count` reads return the flipped bit while the stored value stays right
+    Read { count: u32 },
+    /// the next `count` writes store the flipped bit
+    Write { count: u32 },
+    /// every read returns the flipped bit, as from a cell which can't hold the written value
+    Stuck,
+}
+
+/// Bit flip injected into the simulated memory
+#[derive(Copy, Clone, Debug)]
+pub struct BitFlip {
+    /// byte offset in the tested memory, rounded down to the 4-byte value
+    pub address: i64,
+    pub bit: u32,
+    /// first iteration affected by the flip. The allocation check runs as iteration 1 too, and
+    /// the re-read window always keeps the data of iteration 1.
+    pub from_iteration: u32,
+    pub kind: FlipKind,
+}
+
+/// Failure of the whole device on a dispatch
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DeviceFailure {
+    Lost,
+    Hang,
+}
+
+/// Parameters and injected faults of a simulated device, tested with
+/// [`crate::TestSession::run_simulated`]
+///
+/// ```
+/// use memtest_vulkan::simulated::{BitFlip, FlipKind, SimulatedDevice};
+///
+/// let device = SimulatedDevice::new(512 * 1024 * 1024).flip(BitFlip {
+///     address: 0x1000_0000,
+///     bit: 3,
+///     from_iteration: 2,
+///     kind: FlipKind::Stuck,
+/// });
+/// let session = memtest_vulkan::TestSession::builder()
+///     .iterations(2)
+///     .build()
+///     .unwrap();
+/// let outcome = session.run_simulated(&device, std::io::sink(), &mut ());
+/// assert_eq!(outcome.verdict, memtest_vulkan::Verdict::ErrorsFound);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SimulatedDevice {
+    memory_size: i64,
+    max_allocation_size: i64,
+    failed_allocations: u32,
+    flips: Vec<BitFlip>,
+    failure: Option<(u64, DeviceFailure)>,
+}
+
+impl SimulatedDevice {
+    /// Device with `memory_size` bytes available for the test allocations
+    pub fn new(memory_size: i64) -> Self {
+        Self {
+            memory_size,
+            max_allocation_size: i64::MAX,
+            failed_allocations: 0,
+            flips: Vec::new(),
+            failure: None,
+        }
+    }
+
+    pub fn memory_size(&self) -> i64 {
+        self.memory_size
+    }
+
+    /// Biggest single allocation as reported by the device
+    pub fn max_allocation_size(mut self, bytes: i64) -> Self {
+        self.max_allocation_size = bytes;
+        self
+    }
+
+    /// Makes the first `count` allocations fail as out of device memory
+    pub fn fail_allocations(mut self, count: u32) -> Self {
+        self.failed_allocations = count;
+        self
+    }
+
+    pub fn flip(mut self, flip: BitFlip) -> Self {
+        self.flips.push(flip);
+        self
+    }
+
+    /// Makes the dispatch with given number, counting from 1, fail with the device failure
+    pub fn fail_dispatch(mut self, dispatch: u64, failure: DeviceFailure) -> Self {
+        self.failure = Some((dispatch, failure));
+        self
+    }
+}
+
+/// Injected fault changing every vector of the window on one path, CPU port of `fault_addr`
+/// and `fault_value` shader functions. Bit flips are handled as the sparse cells instead.
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct DataFault {
+    model: u32,
+    addr: u32,
+    bit: u32,
+    rate: u32,
+    seed: u32,
+}
+
+impl DataFault {
+    fn from_io(io: &IOBuf, path: u32) -> Option<Self> {
+        let models = [
+            inject::FAULT_STUCK_AT_0,
+            inject::FAULT_STUCK_AT_1,
+            inject::FAULT_ALIAS,
+            inject::FAULT_STORM,
+        ];
+        (io.fault_path == path && models.contains(&io.fault_model)).then_some(Self {
+            model: io.fault_model,
+            addr: io.fault_addr,
+            bit: io.fault_bit,
+            rate: io.fault_rate,
+            seed: io.fault_seed,
+        })
+    }
+
+    /// Index of the u32 accessed instead of the one with `index` in the window of `vectors`
+    fn index(&self, index: u32, vectors: u32) -> u32 {
+        if self.model == inject::FAULT_ALIAS {
+            let aliased = (index / 4) ^ self.addr;
+            if aliased < vectors {
+                return aliased * 4 + index % 4;
+            }
+        }
+        index
+    }
+
+    /// Value of the u32 with `index` in the window changed by the fault
+    fn value(&self, index: u32, value: u32) -> u32 {
+        let lane_bit = 1 << (self.bit % 32);
+        let in_lane = index % 4 == self.bit / 32;
+        match self.model {
+            inject::FAULT_STUCK_AT_0 if in_lane => value & !lane_bit,
+            inject::FAULT_STUCK_AT_1 if in_lane => value | lane_bit,
+            inject::FAULT_STORM => {
+                let hash = fault_hash(index ^ self.seed);
+                if hash < self.rate {
+                    value ^ 1 << (fault_hash(hash) % 32)
+                } else {
+                    value
+                }
+            }
+            _ => value,
+        }
+    }
+}
+
+/// Same as `fault_hash` shader function
+fn fault_hash(value: u32) -> u32 {
+    let mut x = value;
+    x = (x ^ (x >> 16)).wrapping_mul(0x7FEB352D);
+    x = (x ^ (x >> 15)).wrapping_mul(0x846CA68B);
+    x ^ (x >> 16)
+}
+
+/// Content written to a whole window by a kernel
+#[derive(Copy, Clone, PartialEq, Eq)]
+struct Fill {
+    pattern: TestPattern,
+    calc_param: u32,
+    inverted: bool,
+    /// write fault of the kernel and the vectors count of the window
+    fault: Option<(DataFault, u32)>,
+}
+
+impl Fill {
+    fn from_io(io: &IOBuf, inverted: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            pattern: TestPattern::from_index(io.pattern).ok_or("unknown test pattern")?,
+            calc_param: io.calc_param,
+            inverted,
+            fault: None,
+        })
+    }
+
+    /// Value of the u32 with given index inside the window, same as `test_value_by_index`
+    fn value(&self, index: u32) -> u32 {
+        let value = self
+            .pattern
+            .value(index.wrapping_add(self.calc_param).wrapping_add(1));
+        if self.inverted {
+            !value
+        } else {
+            value
+        }
+    }
+
+    /// Value actually stored by the kernel at the index, with its write fault applied
+    fn stored_value(&self, index: u32) -> u32 {
+        match self.fault {
+            Some((fault, vectors)) => {
+                let source = fault.index(index, vectors);
+                fault.value(source, self.value(source))
+            }
+            None => self.value(index),
+        }
+    }
+}
+
+/// Backend running the kernels on the CPU. Windows keep their last fill, only the values
+/// differing from it are stored.
+pub(crate) struct SimulatedBackend<'a> {
+    device: &'a SimulatedDevice,
+    hang_timeout: time::Duration,
+    io: RefCell<Box<IOBuf>>,
+    allocations: RefCell<Vec<i64>>,
+    allocation_calls: Cell<u32>,
+    dispatches: Cell<u64>,
+    /// by test offset of the window
+    fills: RefCell<HashMap<i64, Fill>>,
+    /// by index of the u32 in the tested memory
+    cells: RefCell<BTreeMap<i64, u32>>,
+    flips_left: RefCell<Vec<u32>>,
+}
+
+impl<'a> SimulatedBackend<'a> {
+    pub fn new(device: &'a SimulatedDevice, hang_timeout: time::Duration) -> Self {
+        Self {
+            device,
+            hang_timeout,
+            io: RefCell::new(Box::default()),
+            allocations: RefCell::new(Vec::new()),
+            allocation_calls: Cell::new(0),
+            dispatches: Cell::new(0),
+            fills: RefCell::new(HashMap::new()),
+            cells: RefCell::new(BTreeMap::new()),
+            flips_left: RefCell::new(
+                device
+                    .flips
+                    .iter()
+                    .map(|flip| match flip.kind {
+                        FlipKind::Read { count } | FlipKind::Write { count } => count,
+                        FlipKind::Stuck => 0,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    fn flip_index(flip: &BitFlip) -> i64 {
+        flip.address / ELEMENT_SIZE
+    }
+
+    /// Index in the window and mask of the bit flip injected on the path as by the shader
+    fn injected_flip(io: &IOBuf, path: u32) -> Option<(i64, u32)> {
+        (io.fault_model == inject::FAULT_BIT_FLIP && io.fault_path == path)
+            .then(|| (io.fault_addr as i64, 1 << io.fault_bit))
+    }
+
+    /// Compares the window with the expected fill and reports the mismatches as the shader does
+    fn read(&self, io: &mut IOBuf, window: std::ops::Range<i64>, test_offset: i64, expected: Fill) {
+        let stored = self.fills.borrow().get(&test_offset).copied();
+        let cells = self.cells.borrow();
+        let mut flips_left = self.flips_left.borrow_mut();
+        let flipped: Vec<(usize, &BitFlip)> = self
+            .device
+            .flips
+            .iter()
+            .enumerate()
+            .filter(|(_, flip)| window.contains(&Self::flip_index(flip)))
+            .collect();
+        let injected = Self::injected_flip(io, inject::FAULT_ON_READ)
+            .map(|(index, mask)| (window.start + index, mask));
+        let read_fault = DataFault::from_io(io, inject::FAULT_ON_READ);
+        let vectors = ((window.end - window.start) / 4) as u32;
+        let indices: Box<dyn Iterator<Item = i64>> =
+            if stored == Some(expected) && read_fault.is_none() {
+                let candidates: BTreeSet<i64> = cells
+                    .range(window.clone())
+                    .map(|(index, _)| *index)
+                    .chain(flipped.iter().map(|(_, flip)| Self::flip_index(flip)))
+                    .chain(injected.map(|(index, _)| index))
+                    .collect();
+                Box::new(candidates.into_iter())
+            } else {
+                Box::new(window.clone())
+            };
+        for index in indices {
+            let window_index = (index - window.start) as u32;
+            let source = match read_fault {
+                Some(fault) => window.start + fault.index(window_index, vectors) as i64,
+                None => index,
+            };
+            let mut actual = match cells.get(&source) {
+                Some(value) => *value,
+                None => stored.map_or(0, |fill| fill.stored_value((source - window.start) as u32)),
+            };
+            for (flip_idx, flip) in &flipped {
+                if Self::flip_index(flip) != source || io.iter < flip.from_iteration {
+                    continue;
+                }
+                match flip.kind {
+                    FlipKind::Stuck => actual ^= 1 << flip.bit,
+                    FlipKind::Read { .. } if flips_left[*flip_idx] > 0 => {
+                        flips_left[*flip_idx] -= 1;
+                        actual ^= 1 << flip.bit;
+                    }
+                    _ => {}
+                }
+            }
+            if let Some((_, mask)) = injected.filter(|(injected, _)| *injected == index) {
+                actual ^= mask;
+            }
+            if let Some(fault) = read_fault {
+                actual = fault.value(window_index, actual);
+            }
+            let expected_value = expected.value(window_index);
+            if actual != expected_value {
+                report_error(io, window_index, actual, expected_value);
+            }
+        }
+        io.done_iter_or_err = io.done_iter_or_err.max(io.iter);
+    }
+
+    /// Fills the window, values of the pending write flips and the write fault are stored wrong
+    fn write(&self, io: &IOBuf, window: std::ops::Range<i64>, test_offset: i64, fill: Fill) {
+        let vectors = ((window.end - window.start) / 4) as u32;
+        let fill = Fill {
+            fault: DataFault::from_io(io, inject::FAULT_ON_WRITE).map(|fault| (fault, vectors)),
+            ..fill
+        };
+        self.fills.borrow_mut().insert(test_offset, fill);
+        let mut cells = self.cells.borrow_mut();
+        let stale: Vec<i64> = cells
+            .range(window.clone())
+            .map(|(index, _)| *index)
+            .collect();
+        for index in stale {
+            cells.remove(&index);
+        }
+        let mut store_flipped = |index: i64, mask: u32| {
+            let value = cells
+                .get(&index)
+                .copied()
+                .unwrap_or_else(|| fill.stored_value((index - window.start) as u32));
+            cells.insert(index, value ^ mask);
+        };
+        let mut flips_left = self.flips_left.borrow_mut();
+        for (flip_idx, flip) in self.device.flips.iter().enumerate() {
+            let index = Self::flip_index(flip);
+            if !window.contains(&index)
+                || io.iter < flip.from_iteration
+                || !matches!(flip.kind, FlipKind::Write { .. })
+                || flips_left[flip_idx] == 0
+            {
+                continue;
+            }
+            flips_left[flip_idx] -= 1;
+            store_flipped(index, 1 << flip.bit);
+        }
+        if let Some((index, mask)) = Self::injected_flip(io, inject::FAULT_ON_WRITE) {
+            store_flipped(window.start + index, mask);
+        }
+    }
+
+    fn check_failure(
+        &self,
+        kernel: Kernel,
+        window_idx: i64,
+        test_offset: i64,
+        iteration: u32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let dispatch = self.dispatches.get() + 1;
+        self.dispatches.set(dispatch);
+        match self.device.failure {
+            Some((failing, DeviceFailure::Lost)) if failing == dispatch => {
+                Err(Box::new(DeviceLost {
+                    context: "simulated dispatch",
+                    pipeline: kernel.name(),
+                    window_idx,
+                    iteration,
+                }))
+            }
+            Some((failing, DeviceFailure::Hang)) if failing == dispatch => Err(Box::new(GpuHang {
+                pipeline: kernel.name(),
+                window_idx,
+                test_offset,
+                iteration,
+                timeout: self.hang_timeout,
+            })),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// CPU port of `report_errors` shader function for a single u32 with given index in the window
+fn report_error(io: &mut IOBuf, index: u32, actual: u32, expected: u32) {
+    let lane = index as usize % 4 * ELEMENT_BIT_SIZE;
+    let error_mask = actual ^ expected;
+    let one_bits = error_mask.count_ones();
+    if one_bits == 1 {
+        io.err_bit1_idx.0[lane + error_mask.trailing_zeros() as usize] += 1;
+    }
+    io.err_bitcount.0[lane + one_bits as usize % ELEMENT_BIT_SIZE] += 1;
+    io.idx_max = io.idx_max.max(index);
+    io.idx_min = io.idx_min.min(index);
+    if let Some(bucket_offset) = (io.heat_phase + index).checked_div(io.heat_bucket_values) {
+        let heat_bucket = io.heat_base + bucket_offset;
+        if (heat_bucket as usize) < heatmap::HEAT_MAP_BUCKETS {
+            io.err_heat.0[heat_bucket as usize] += 1;
+        }
+    }
+    let mut value = ((io.value_base_hi as u64) << 32 | io.value_base_lo as u64) + index as u64;
+    while value != 0 {
+        io.err_addr_bit1.0[value.trailing_zeros() as usize] += 1;
+        value &= value - 1;
+    }
+    let capture_idx = io.err_capture_count as usize;
+    io.err_capture_count += 1;
+    if capture_idx < ERR_CAPTURE_SIZE {
+        let record = &mut io.err_capture.0[capture_idx];
+        record.vec_addr = index;
+        record.expected = expected;
+        record.actual = actual;
+        record.xor_mask = error_mask;
+    }
+    io.done_iter_or_err = u32::MAX;
+    let actual_bits = actual.count_ones();
+    if actual_bits == 32 {
+        io.actual_ff += 1;
+    } else {
+        io.mem_bitcount.0[actual_bits as usize] += 1;
+        io.actual_max = io.actual_max.max(actual);
+        io.actual_min = io.actual_min.min(actual);
+    }
+}
+
+impl Backend for SimulatedBackend<'_> {
+    fn max_allocation_size(&self) -> i64 {
+        self.device.max_allocation_size
+    }
+
+    fn memory_type(&self) -> u32 {
+        0
+    }
+
+    fn describe_memory(&self) -> String {
+        format!("simulated memory of {} bytes", self.device.memory_size)
+    }
+
+    fn allocate(&self, size: i64) -> Result<(), AllocationError> {
+        let calls = self.allocation_calls.get() + 1;
+        self.allocation_calls.set(calls);
+        let mut allocations = self.allocations.borrow_mut();
+        if calls <= self.device.failed_allocations
+            || allocations.iter().sum::<i64>() + size > self.device.memory_size
+        {
+            return Err(AllocationError::Memory(
+                format!("simulated out of device memory allocating {size} bytes").into(),
+            ));
+        }
+        allocations.push(size);
+        Ok(())
+    }
+
+    fn free_allocations(&self) {
+        self.allocations.borrow_mut().clear();
+        self.fills.borrow_mut().clear();
+        self.cells.borrow_mut().clear();
+    }
+
+    fn read_io(&self) -> IOBuf {
+        **self.io.borrow()
+    }
+
+    fn write_io(&self, io: &IOBuf) {
+        **self.io.borrow_mut() = *io;
+    }
+
+    fn update_io(&self, update: &mut dyn FnMut(&mut IOBuf)) {
+        update(&mut self.io.borrow_mut());
+    }
+
+    fn dispatch(
+        &self,
+        kernel: Kernel,
+        layout: &AllocationLayout,
+        test_offset: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut io = self.io.borrow_mut();
+        let window_idx = test_offset / layout.window_size;
+        self.check_failure(kernel, window_idx, test_offset, io.iter)?;
+        let (allocation_idx, _) = layout.locate(test_offset);
+        if allocation_idx >= self.allocations.borrow().len() {
+            return Err("dispatch outside of the test allocations".into());
+        }
+        let start = test_offset / ELEMENT_SIZE;
+        let window = start..start + layout.window_size / ELEMENT_SIZE;
+        match kernel {
+            Kernel::Read => {
+                let expected = Fill::from_io(&io, false)?;
+                self.read(&mut io, window, test_offset, expected);
+                io.first_elem.0 = [4, 5, 6, 7].map(|index| expected.value(index));
+            }
+            Kernel::Write => self.write(&io, window, test_offset, Fill::from_io(&io, false)?),
+            Kernel::March => {
+                let op = io.march_op;
+                if op & (modes::MARCH_READ_BACKGROUND | modes::MARCH_READ_INVERTED) != 0 {
+                    let expected = Fill::from_io(&io, op & modes::MARCH_READ_INVERTED != 0)?;
+                    self.read(&mut io, window.clone(), test_offset, expected);
+                }
+                if op & modes::MARCH_WRITE_BACKGROUND != 0 {
+                    self.write(&io, window, test_offset, Fill::from_io(&io, false)?);
+                } else if op & modes::MARCH_WRITE_INVERTED != 0 {
+                    self.write(&io, window, test_offset, Fill::from_io(&io, true)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn wait_idle(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(())
+    }
+}
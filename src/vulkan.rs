@@ -0,0 +1,486 @@
+use crate::{
+    allocation::AllocationLayout,
+    backend::{AllocationError, Backend, Kernel},
+    engine::{
+        DeviceLost, GpuHang, IOBuf, MapErrStr, ELEMENT_SIZE, MIN_WANTED_ALLOCATION, READ_SHADER,
+        TEST_WINDOW_1D_MAX_GROUPS, VEC_SIZE, WG_SIZE,
+    },
+    events,
+    inject::CallFault,
+    output,
+};
+use erupt::{utils::VulkanResult, vk, DeviceLoader};
+use std::{
+    cell::{Cell, RefCell},
+    io::Write,
+    mem, time,
+};
+
+/// Compute pipelines and buffers of the test shader on a Vulkan device
+pub(crate) struct VulkanBackend<'a> {
+    device: &'a DeviceLoader,
+    queue: vk::Queue,
+    cmd_pool: vk::CommandPool,
+    cmd_bufs: erupt::SmallVec<vk::CommandBuffer>,
+    desc_pool: vk::DescriptorPool,
+    desc_layout: vk::DescriptorSetLayout,
+    desc_sets: erupt::SmallVec<vk::DescriptorSet>,
+    pipeline_layout: vk::PipelineLayout,
+    shader_mod: vk::ShaderModule,
+    pipelines: Vec<vk::Pipeline>,
+    io_buffer: vk::Buffer,
+    io_memory: vk::DeviceMemory,
+    mapped: *mut IOBuf,
+    fence: vk::Fence,
+    memory_props: vk::PhysicalDeviceMemoryProperties,
+    test_mem_index: u32,
+    max_allocation_size: i64,
+    hang_timeout: time::Duration,
+    test_buffers: RefCell<Vec<vk::Buffer>>,
+    test_memories: RefCell<Vec<vk::DeviceMemory>>,
+    call_fault: Cell<Option<CallFault>>,
+    iteration: Cell<u32>,
+}
+
+impl<'a> VulkanBackend<'a> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<Writer: std::io::Write>(
+        device: &'a DeviceLoader,
+        queue_family_index: u32,
+        memory_props: vk::PhysicalDeviceMemoryProperties,
+        max_allocation_size: i64,
+        hang_timeout: time::Duration,
+        verbose: bool,
+        log_dupler: &mut output::LogDupler<Writer>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let call_fault =
+            CallFault::from_env().map_err(|e| format!("{}: {e}", CallFault::ENV_VAR))?;
+        if let Some(fault) = call_fault {
+            events::Event::new("fault_injection")
+                .str("call", &fault.to_string())
+                .emit();
+            let _ = writeln!(log_dupler, "Injecting call fault {fault}");
+        }
+        let queue = unsafe { device.get_device_queue(queue_family_index, 0) };
+
+        let cmd_pool_info = vk::CommandPoolCreateInfoBuilder::new()
+            .queue_family_index(queue_family_index)
+            .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
+        let cmd_pool = unsafe { device.create_command_pool(&cmd_pool_info, None) }.err_as_str()?;
+
+        let cmd_buf_info = vk::CommandBufferAllocateInfoBuilder::new()
+            .command_pool(cmd_pool)
+            .command_buffer_count(1)
+            .level(vk::CommandBufferLevel::PRIMARY);
+        let cmd_bufs = unsafe { device.allocate_command_buffers(&cmd_buf_info) }.err_as_str()?;
+
+        let desc_pool_sizes = &[vk::DescriptorPoolSizeBuilder::new()
+            .descriptor_count(2)
+            ._type(vk::DescriptorType::STORAGE_BUFFER)];
+        let desc_pool_info = vk::DescriptorPoolCreateInfoBuilder::new()
+            .pool_sizes(desc_pool_sizes)
+            .max_sets(1);
+        let desc_pool =
+            unsafe { device.create_descriptor_pool(&desc_pool_info, None) }.err_as_str()?;
+
+        let desc_layout_bindings = &[
+            vk::DescriptorSetLayoutBindingBuilder::new()
+                .binding(0)
+                .descriptor_count(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+            vk::DescriptorSetLayoutBindingBuilder::new()
+                .binding(1)
+                .descriptor_count(1)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE),
+        ];
+        let desc_layout_info =
+            vk::DescriptorSetLayoutCreateInfoBuilder::new().bindings(desc_layout_bindings);
+        let desc_layouts =
+            [
+                unsafe { device.create_descriptor_set_layout(&desc_layout_info, None) }
+                    .err_as_str()?,
+            ];
+
+        let desc_info = vk::DescriptorSetAllocateInfoBuilder::new()
+            .descriptor_pool(desc_pool)
+            .set_layouts(&desc_layouts);
+        let desc_sets = unsafe { device.allocate_descriptor_sets(&desc_info) }.err_as_str()?;
+
+        let pipeline_layout_info =
+            vk::PipelineLayoutCreateInfoBuilder::new().set_layouts(&desc_layouts);
+        let pipeline_layout =
+            unsafe { device.create_pipeline_layout(&pipeline_layout_info, None) }.err_as_str()?;
+
+        let spv_code = Vec::from(READ_SHADER);
+        let create_info = vk::ShaderModuleCreateInfoBuilder::new().code(&spv_code);
+        let shader_mod = unsafe { device.create_shader_module(&create_info, None) }.err_as_str()?;
+
+        let pipeline_infos = Kernel::ALL.map(|kernel| {
+            let shader_stage = vk::PipelineShaderStageCreateInfoBuilder::new()
+                .stage(vk::ShaderStageFlagBits::COMPUTE)
+                .module(shader_mod)
+                .name(kernel.entry_point());
+            vk::ComputePipelineCreateInfoBuilder::new()
+                .layout(pipeline_layout)
+                .stage(*shader_stage)
+        });
+        let pipelines =
+            unsafe { device.create_compute_pipelines(Default::default(), &pipeline_infos, None) }
+                .err_as_str()?
+                .to_vec();
+
+        let io_data_size = mem::size_of::<IOBuf>() as vk::DeviceSize;
+
+        let io_buffer_create_info = vk::BufferCreateInfoBuilder::new()
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+            .size(io_data_size);
+        let io_buffer =
+            unsafe { device.create_buffer(&io_buffer_create_info, None) }.err_as_str()?;
+        let io_mem_reqs = unsafe { device.get_buffer_memory_requirements(io_buffer) };
+        let mut io_mem_indices = Vec::new();
+        for i in 0..memory_props.memory_type_count {
+            //test buffer comptibility flags expressed as bitmask
+            let suitable = (io_mem_reqs.memory_type_bits & (1 << i)) != 0;
+            let memory_type = memory_props.memory_types[i as usize];
+            if verbose && !memory_type.property_flags.is_empty() {
+                let _ = writeln!(log_dupler, "{:2} {:?} ", i, memory_type);
+            }
+            if suitable
+                && memory_type.property_flags.contains(
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL
+                        | vk::MemoryPropertyFlags::HOST_VISIBLE
+                        | vk::MemoryPropertyFlags::HOST_COHERENT,
+                )
+            {
+                io_mem_indices.push(i);
+            }
+        }
+
+        // sorting by a flag value allows selection of index with the minimum count of new unknown flags
+        let io_mem_index = io_mem_indices
+            .into_iter()
+            .min_by_key(|i| memory_props.memory_types[*i as usize].property_flags)
+            .ok_or("This device lacks support for DEVICE_LOCAL+HOST_COHERENT memory type.")?;
+        if verbose {
+            let _ = writeln!(
+                log_dupler,
+                "CoherentIO memory          type {} inside heap {:?}",
+                io_mem_index,
+                memory_props.memory_heaps
+                    [memory_props.memory_types[io_mem_index as usize].heap_index as usize]
+            );
+        }
+
+        let io_memory_allocate_info = vk::MemoryAllocateInfoBuilder::new()
+            .allocation_size(io_mem_reqs.size)
+            .memory_type_index(io_mem_index);
+        let io_memory =
+            unsafe { device.allocate_memory(&io_memory_allocate_info, None) }.err_as_str()?;
+
+        let mapped: *mut IOBuf = unsafe {
+            mem::transmute(
+                device
+                    .map_memory(io_memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::default())
+                    .err_as_str()?,
+            )
+        };
+        unsafe { device.bind_buffer_memory(io_buffer, io_memory, 0) }
+            .err_as_str_context("bind_buffer_memory")?;
+
+        let test_mem_reqs = memory_requirements(device, MIN_WANTED_ALLOCATION)?;
+
+        let test_mem_index = (0..memory_props.memory_type_count)
+            .filter(|i| {
+                //test buffer comptibility flags expressed as bitmask
+                let suitable = (test_mem_reqs.memory_type_bits & (1 << i)) != 0;
+                let memory_type = memory_props.memory_types[*i as usize];
+                suitable
+                    && memory_type
+                        .property_flags
+                        .contains(vk::MemoryPropertyFlags::DEVICE_LOCAL)
+            })
+            .max_by_key(|mem_index| {
+                let mem_type = memory_props.memory_types[*mem_index as usize];
+                let heap_size = memory_props.memory_heaps[mem_type.heap_index as usize].size;
+                // Among greatest heap_size select index with the minimum count of unknown flags
+                (heap_size, std::cmp::Reverse(mem_type.property_flags))
+            })
+            .ok_or("DEVICE_LOCAL test memory type not available")?;
+
+        unsafe {
+            device.update_descriptor_sets(
+                &[vk::WriteDescriptorSetBuilder::new()
+                    .dst_set(desc_sets[0])
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&[vk::DescriptorBufferInfoBuilder::new()
+                        .buffer(io_buffer)
+                        .offset(0)
+                        .range(vk::WHOLE_SIZE)])],
+                &[],
+            );
+        }
+
+        let fence =
+            unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None) }.err_as_str()?;
+
+        Ok(Self {
+            device,
+            queue,
+            cmd_pool,
+            cmd_bufs,
+            desc_pool,
+            desc_layout: desc_layouts[0],
+            desc_sets,
+            pipeline_layout,
+            shader_mod,
+            pipelines,
+            io_buffer,
+            io_memory,
+            mapped,
+            fence,
+            memory_props,
+            test_mem_index,
+            max_allocation_size,
+            hang_timeout,
+            test_buffers: RefCell::new(Vec::new()),
+            test_memories: RefCell::new(Vec::new()),
+            call_fault: Cell::new(call_fault),
+            iteration: Cell::new(0),
+        })
+    }
+
+    /// Takes the injected fault if it fires on this call
+    fn injected(&self, call: &str) -> Option<CallFault> {
+        let fault = self
+            .call_fault
+            .get()
+            .filter(|fault| fault.matches(call, self.iteration.get()))?;
+        self.call_fault.set(None);
+        Some(fault)
+    }
+
+    /// Makes the call unless the injected fault replaces its result
+    fn call<T>(&self, call: &str, make: impl FnOnce() -> VulkanResult<T>) -> VulkanResult<T> {
+        match self.injected(call) {
+            Some(CallFault::Fail { result, .. }) => VulkanResult::new_err(result),
+            _ => make(),
+        }
+    }
+}
+
+fn test_buffer_create_info<'a>(size: i64) -> vk::BufferCreateInfoBuilder<'a> {
+    vk::BufferCreateInfoBuilder::new()
+        .sharing_mode(vk::SharingMode::EXCLUSIVE)
+        .usage(vk::BufferUsageFlags::STORAGE_BUFFER)
+        .size(size as u64)
+}
+
+fn memory_requirements(
+    device: &erupt::DeviceLoader,
+    min_wanted_allocation: i64,
+) -> Result<vk::MemoryRequirements, Box<dyn std::error::Error>> {
+    let test_buffer =
+        unsafe { device.create_buffer(&test_buffer_create_info(min_wanted_allocation), None) }
+            .err_as_str()?;
+    let test_mem_reqs = unsafe { device.get_buffer_memory_requirements(test_buffer) };
+    unsafe { device.destroy_buffer(test_buffer, None) }; //buffer was used for getting memory reuirements. After allocation size may be smaller
+    Ok(test_mem_reqs)
+}
+
+impl Backend for VulkanBackend<'_> {
+    fn max_allocation_size(&self) -> i64 {
+        self.max_allocation_size
+    }
+
+    fn memory_type(&self) -> u32 {
+        self.test_mem_index
+    }
+
+    fn describe_memory(&self) -> String {
+        let memory_type = self.memory_props.memory_types[self.test_mem_index as usize];
+        format!(
+            "type {:2}: {:?} {:?}",
+            self.test_mem_index,
+            memory_type,
+            self.memory_props.memory_heaps[memory_type.heap_index as usize]
+        )
+    }
+
+    fn allocate(&self, size: i64) -> Result<(), AllocationError> {
+        let device = self.device;
+        let some_buffer = self
+            .call("create_buffer", || unsafe {
+                device.create_buffer(&test_buffer_create_info(size), None)
+            })
+            .err_as_str_context("create_buffer")
+            .map_err(AllocationError::Other)?;
+        self.test_buffers.borrow_mut().push(some_buffer);
+        let buffer_mem_reqs = unsafe { device.get_buffer_memory_requirements(some_buffer) };
+        let test_memory_allocate_info = vk::MemoryAllocateInfoBuilder::new()
+            .allocation_size(buffer_mem_reqs.size)
+            .memory_type_index(self.test_mem_index);
+        let some_memory = self
+            .call("allocate_memory", || unsafe {
+                device.allocate_memory(&test_memory_allocate_info, None)
+            })
+            .err_as_str_context("allocate_memory")
+            .map_err(AllocationError::Memory)?;
+        self.test_memories.borrow_mut().push(some_memory);
+        self.call("bind_buffer_memory", || unsafe {
+            device.bind_buffer_memory(some_buffer, some_memory, 0)
+        })
+        .err_as_str_context("bind_buffer_memory")
+        .map_err(AllocationError::Other)
+    }
+
+    fn free_allocations(&self) {
+        for some_buffer in self.test_buffers.borrow_mut().drain(..) {
+            unsafe {
+                self.device.destroy_buffer(some_buffer, None);
+            }
+        }
+        for some_memory in self.test_memories.borrow_mut().drain(..) {
+            unsafe {
+                self.device.free_memory(some_memory, None);
+            }
+        }
+    }
+
+    fn read_io(&self) -> IOBuf {
+        unsafe { std::ptr::read(self.mapped) }
+    }
+
+    fn write_io(&self, io: &IOBuf) {
+        unsafe { std::ptr::write(self.mapped, *io) }
+    }
+
+    fn update_io(&self, update: &mut dyn FnMut(&mut IOBuf)) {
+        update(unsafe { &mut *self.mapped })
+    }
+
+    fn dispatch(
+        &self,
+        kernel: Kernel,
+        layout: &AllocationLayout,
+        test_offset: i64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let device = self.device;
+        let test_element_count = (layout.window_size / ELEMENT_SIZE) as u32;
+        let (allocation_idx, allocation_offset) = layout.locate(test_offset);
+        let iteration = || unsafe { (*self.mapped).iter };
+        unsafe {
+            device.update_descriptor_sets(
+                &[vk::WriteDescriptorSetBuilder::new()
+                    .dst_set(self.desc_sets[0])
+                    .dst_binding(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&[vk::DescriptorBufferInfoBuilder::new()
+                        .buffer(self.test_buffers.borrow()[allocation_idx])
+                        .offset(allocation_offset as u64)
+                        .range(layout.window_size as u64)])],
+                &[],
+            );
+            let cmd_buf = self.cmd_bufs[0];
+            device
+                .begin_command_buffer(cmd_buf, &vk::CommandBufferBeginInfo::default())
+                .err_as_str_context("begin_command_buffer")?;
+            device.cmd_bind_pipeline(
+                cmd_buf,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipelines[kernel as usize],
+            );
+            device.cmd_bind_descriptor_sets(
+                cmd_buf,
+                vk::PipelineBindPoint::COMPUTE,
+                self.pipeline_layout,
+                0,
+                &self.desc_sets,
+                &[],
+            );
+            device.cmd_dispatch(
+                cmd_buf,
+                (TEST_WINDOW_1D_MAX_GROUPS / WG_SIZE) as u32,
+                test_element_count / VEC_SIZE as u32 / TEST_WINDOW_1D_MAX_GROUPS as u32,
+                1,
+            );
+            device
+                .end_command_buffer(cmd_buf)
+                .err_as_str_context("end_command_buffer")?;
+            let device_lost = |context| DeviceLost {
+                context,
+                pipeline: kernel.name(),
+                window_idx: test_offset / layout.window_size,
+                iteration: iteration(),
+            };
+            let submit_info = &[vk::SubmitInfoBuilder::new().command_buffers(&self.cmd_bufs)];
+            let submit_result = match self.injected("queue_submit") {
+                Some(CallFault::Fail { result, .. }) => VulkanResult::new_err(result),
+                //not submitted, so the fence is never signalled
+                Some(CallFault::Hang { .. }) => VulkanResult::new_ok(()),
+                None => device.queue_submit(self.queue, submit_info, self.fence),
+            };
+            if submit_result.raw == vk::Result::ERROR_DEVICE_LOST {
+                return Err(device_lost("queue_submit").into());
+            }
+            submit_result.err_as_str_context("queue_submit")?;
+            let wait_result = self.call("wait_for_fences", || {
+                device.wait_for_fences(
+                    &[self.fence],
+                    true,
+                    self.hang_timeout.as_nanos().try_into().unwrap_or(u64::MAX),
+                )
+            });
+            if wait_result.raw == vk::Result::TIMEOUT {
+                return Err(GpuHang {
+                    pipeline: kernel.name(),
+                    window_idx: test_offset / layout.window_size,
+                    test_offset,
+                    iteration: iteration(),
+                    timeout: self.hang_timeout,
+                }
+                .into());
+            }
+            if wait_result.raw == vk::Result::ERROR_DEVICE_LOST {
+                return Err(device_lost("wait_for_fences").into());
+            }
+            wait_result.err_as_str_context("wait_for_fences")?;
+            self.call("reset_fences", || device.reset_fences(&[self.fence]))
+                .err_as_str_context("reset_fences")?;
+        }
+        Ok(())
+    }
+
+    fn wait_idle(&self) -> Result<(), Box<dyn std::error::Error>> {
+        unsafe { self.device.device_wait_idle() }.err_as_str()
+    }
+
+    fn set_iteration(&self, iteration: u32) {
+        self.iteration.set(iteration);
+    }
+}
+
+impl Drop for VulkanBackend<'_> {
+    fn drop(&mut self) {
+        let device = self.device;
+        let _ = self.wait_idle();
+        self.free_allocations();
+        unsafe {
+            device.destroy_buffer(self.io_buffer, None);
+            device.unmap_memory(self.io_memory);
+            device.free_memory(self.io_memory, None);
+            device.destroy_fence(self.fence, None);
+            for pipeline in &self.pipelines {
+                device.destroy_pipeline(*pipeline, None);
+            }
+            device.destroy_shader_module(self.shader_mod, None);
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+            device.destroy_descriptor_set_layout(self.desc_layout, None);
+            device.destroy_descriptor_pool(self.desc_pool, None);
+            device.destroy_command_pool(self.cmd_pool, None);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! End-to-end runs of the command line tool on the lavapipe (llvmpipe) software Vulkan driver.
+//! Tests are ignored by default, so a plain `cargo test` doesn't pass them without the driver;
+//! run them with `cargo test -- --include-ignored`. Then they are still skipped if the driver
+//! isn't installed, unless MEMTEST_VULKAN_REQUIRE_LAVAPIPE is set.
+
+use memtest_vulkan::close::app_status;
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+const ICD_DIRS: [&str; 4] = [
+    "/usr/share/vulkan/icd.d",
+    "/usr/local/share/vulkan/icd.d",
+    "/etc/vulkan/icd.d",
+    "/usr/lib/x86_64-linux-gnu/vulkan/icd.d",
+];
+
+/// Driver manifest of lavapipe, None if the test should be skipped
+fn lavapipe_icd() -> Option<PathBuf> {
+    let icd = ICD_DIRS
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.starts_with("lvp_icd"))
+        });
+    if icd.is_none() {
+        assert!(
+            env::var_os("MEMTEST_VULKAN_REQUIRE_LAVAPIPE").is_none(),
+            "lavapipe driver manifest not found in {ICD_DIRS:?}"
+        );
+        eprintln!("lavapipe isn't installed, test skipped");
+    }
+    icd
+}
+
+struct Run {
+    stdout: String,
+    status: u8,
+}
+
+/// Runs the non-interactive tool with lavapipe as the only visible device
+fn run_tool(icd: &PathBuf, args: &[&str], envs: &[(&str, &str)]) -> Run {
+    let Output { status, stdout, .. } = Command::new(env!("CARGO_BIN_EXE_memtest_vulkan"))
+        .args(["--non-interactive", "--no-log", "--memory", "512MB"])
+        .args(args)
+        .env("VK_DRIVER_FILES", icd)
+        .env("VK_ICD_FILENAMES", icd)
+        .envs(envs.iter().copied())
+        .output()
+        .expect("memtest_vulkan binary can't be started");
+    let stdout = String::from_utf8_lossy(&stdout).into_owned();
+    let status = status.code().expect("memtest_vulkan killed by signal") as u8;
+    assert_eq!(
+        status & app_status::SIGNATURE_MASK,
+        app_status::SIGNATURE,
+        "exit status isn't a status byte: {status:#X}\n{stdout}"
+    );
+    Run { stdout, status }
+}
+
+fn has_bits(status: u8, bits: u8) -> bool {
+    status & bits == bits
+}
+
+#[test]
+#[ignore = "needs the lavapipe driver, run with --include-ignored"]
+fn lists_lavapipe_device() {
+    let icd = match lavapipe_icd() {
+        Some(icd) => icd,
+        None => return,
+    };
+    let run = run_tool(&icd, &["--iterations", "1"], &[]);
+    assert!(
+        run.stdout
+            .lines()
+            .any(|line| line.starts_with("1: ") && line.contains("llvmpipe")),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+#[ignore = "needs the lavapipe driver, run with --include-ignored"]
+fn standard_test_passes() {
+    let icd = match lavapipe_icd() {
+        Some(icd) => icd,
+        None => return,
+    };
+    let run = run_tool(&icd, &["--iterations", "3"], &[]);
+    assert!(
+        run.stdout.contains("      1 iteration. Passed"),
+        "{}",
+        run.stdout
+    );
+    assert!(run.stdout.contains("testing PASSed"), "{}", run.stdout);
+    assert!(
+        has_bits(run.status, app_status::INITED_OK),
+        "{}",
+        run.stdout
+    );
+    assert_eq!(
+        run.status & (app_status::RUNTIME_ERRORS | app_status::RUNTIME_ABORT),
+        0,
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+#[ignore = "needs the lavapipe driver, run with --include-ignored"]
+fn emulated_write_bug_is_reported() {
+    let icd = match lavapipe_icd() {
+        Some(icd) => icd,
+        None => return,
+    };
+    let run = run_tool(
+        &icd,
+        &["--iterations", "3"],
+        &[("MEMTEST_VULKAN_EMULATE_WRITE_BUG_ITERATION", "2")],
+    );
+    assert!(
+        run.stdout.contains("Error found. Mode INITIAL_READ"),
+        "{}",
+        run.stdout
+    );
+    assert!(run.stdout.contains("WRITE-PATH"), "{}", run.stdout);
+    assert!(run.stdout.contains("ERRORS FOUND"), "{}", run.stdout);
+    assert!(
+        has_bits(
+            run.status,
+            app_status::INITED_OK | app_status::RUNTIME_ERRORS
+        ),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+#[ignore = "needs the lavapipe driver, run with --include-ignored"]
+fn injected_stuck_lane_is_diagnosed() {
+    let icd = match lavapipe_icd() {
+        Some(icd) => icd,
+        None => return,
+    };
+    let run = run_tool(
+        &icd,
+        &["--iterations", "2", "--inject", "stuck-at-1:37@2"],
+        &[],
+    );
+    assert!(
+        run.stdout.contains("Injecting fault stuck-at-1:37/write@2"),
+        "{}",
+        run.stdout
+    );
+    assert!(
+        run.stdout
+            .contains("single data line fault on bit 5 of y component (lane 37)"),
+        "{}",
+        run.stdout
+    );
+    assert!(
+        has_bits(run.status, app_status::RUNTIME_ERRORS),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+#[ignore = "needs the lavapipe driver, run with --include-ignored"]
+fn injected_hang_is_reported() {
+    let icd = match lavapipe_icd() {
+        Some(icd) => icd,
+        None => return,
+    };
+    let run = run_tool(
+        &icd,
+        &["--iterations", "3", "--hang-timeout", "2s"],
+        &[("MEMTEST_VULKAN_INJECT_CALL", "hang@2")],
+    );
+    assert!(
+        run.stdout.contains("Injecting call fault hang@2"),
+        "{}",
+        run.stdout
+    );
+    assert!(run.stdout.contains("GPU HANG detected"), "{}", run.stdout);
+    assert!(
+        has_bits(run.status, app_status::RUNTIME_ABORT),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+#[ignore = "needs the lavapipe driver, run with --include-ignored"]
+fn lost_device_is_respawned() {
+    let icd = match lavapipe_icd() {
+        Some(icd) => icd,
+        None => return,
+    };
+    let run = run_tool(
+        &icd,
+        &["--iterations", "3", "--device-lost-retries", "2"],
+        &[(
+            "MEMTEST_VULKAN_INJECT_CALL",
+            "queue_submit:ERROR_DEVICE_LOST@2",
+        )],
+    );
+    //each respawned worker loses the device again
+    assert!(
+        run.stdout
+            .contains("Device lost 2 time(s), respawning test process"),
+        "{}",
+        run.stdout
+    );
+    assert!(
+        run.stdout.contains("DEVICE LOST during testing"),
+        "{}",
+        run.stdout
+    );
+    assert!(
+        has_bits(run.status, app_status::RUNTIME_ABORT),
+        "{}",
+        run.stdout
+    );
+}
+
+#[test]
+#[ignore = "needs the lavapipe driver, run with --include-ignored"]
+fn failed_allocations_reduce_memory_down_to_minimum() {
+    let icd = match lavapipe_icd() {
+        Some(icd) => icd,
+        None => return,
+    };
+    let run = run_tool(
+        &icd,
+        &["--iterations", "1"],
+        &[(
+            "MEMTEST_VULKAN_INJECT_CALL",
+            "bind_buffer_memory:ERROR_OUT_OF_DEVICE_MEMORY@0",
+        )],
+    );
+    //every worker fails its first allocation, then so does the in-process test of the minimum
+    assert!(
+        run.stdout
+            .contains("Using in-process testing method with small memory limit"),
+        "{}",
+        run.stdout
+    );
+    assert!(run.stdout.contains("bind_buffer_memory"), "{}", run.stdout);
+    assert!(
+        has_bits(run.status, app_status::RUNTIME_ABORT),
+        "{}",
+        run.stdout
+    );
+    assert!(
+        !has_bits(run.status, app_status::INITED_OK),
+        "{}",
+        run.stdout
+    );
+}
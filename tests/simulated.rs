@@ -0,0 +1,356 @@
+use memtest_vulkan::{
+    inject::{FaultModel, FaultPath, FaultSpec},
+    modes::TestMode,
+    simulated::{BitFlip, DeviceFailure, FlipKind, SimulatedDevice},
+    AllocationFailed, ErrorBlock, ProbeSucceeded, Progress, TestConfig, TestObserver, TestSession,
+    Verdict,
+};
+use std::{sync::Mutex, time};
+
+const MB: i64 = 1024 * 1024;
+/// start of the second window of 512MB memory, the first one is the re-read window
+const SECOND_WINDOW: i64 = 256 * MB;
+
+//testing status is process-wide, so sessions can't run in parallel
+static SESSION_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Default)]
+struct Recorder {
+    progress_reports: usize,
+    errors: Vec<(i64, i64, i64)>,
+    modes: Vec<String>,
+    top_findings: Vec<String>,
+    verdict: Option<Verdict>,
+}
+
+impl TestObserver for Recorder {
+    fn on_progress(&mut self, _progress: &Progress) {
+        self.progress_reports += 1;
+    }
+    fn on_error(&mut self, error: &ErrorBlock) {
+        self.errors
+            .push((error.address_first, error.address_last, error.total_errors));
+        self.modes.push(error.mode.to_string());
+        if let Some(finding) = error.diagnosis.first() {
+            self.top_findings.push(finding.description.clone());
+        }
+    }
+    fn on_verdict(&mut self, verdict: Verdict) {
+        self.verdict = Some(verdict);
+    }
+}
+
+struct Run {
+    verdict: Verdict,
+    log: String,
+    recorder: Recorder,
+}
+
+fn run(device: &SimulatedDevice, config: TestConfig) -> Run {
+    let _lock = SESSION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let session = TestSession::builder().config(config).build().unwrap();
+    let mut log = Vec::new();
+    let mut recorder = Recorder::default();
+    let outcome = session.run_simulated(device, &mut log, &mut recorder);
+    Run {
+        verdict: outcome.verdict,
+        log: String::from_utf8(log).unwrap(),
+        recorder,
+    }
+}
+
+fn config(iterations: i32) -> TestConfig {
+    TestConfig {
+        max_iterations: Some(iterations),
+        verbosity: 1,
+        ..TestConfig::default()
+    }
+}
+
+fn flip(kind: FlipKind) -> BitFlip {
+    BitFlip {
+        address: SECOND_WINDOW + 0x1234,
+        bit: 5,
+        from_iteration: 2,
+        kind,
+    }
+}
+
+#[test]
+fn clean_device_passes() {
+    let result = run(&SimulatedDevice::new(512 * MB), config(3));
+    assert_eq!(result.verdict, Verdict::Passed, "{}", result.log);
+    assert_eq!(result.recorder.verdict, Some(Verdict::Passed));
+    assert!(result.recorder.progress_reports > 0);
+    assert!(result.recorder.errors.is_empty());
+}
+
+#[test]
+fn stuck_bit_is_reported_at_its_address() {
+    let flip = flip(FlipKind::Stuck);
+    let result = run(&SimulatedDevice::new(512 * MB).flip(flip), config(3));
+    assert_eq!(result.verdict, Verdict::ErrorsFound);
+    assert_eq!(result.recorder.errors.len(), 2, "{}", result.log);
+    for (address_first, address_last, total_errors) in result.recorder.errors {
+        assert_eq!(address_first, flip.address);
+        assert_eq!(address_last, flip.address + 3); //last byte of the value
+        assert_eq!(total_errors, 1);
+    }
+    assert!(result.log.contains("STICKY"), "{}", result.log);
+}
+
+#[test]
+fn read_glitch_is_transient() {
+    let device = SimulatedDevice::new(512 * MB).flip(flip(FlipKind::Read { count: 1 }));
+    let result = run(&device, config(3));
+    assert_eq!(result.verdict, Verdict::ErrorsFound);
+    assert_eq!(result.recorder.errors.len(), 1);
+    assert!(result.log.contains("TRANSIENT"), "{}", result.log);
+}
+
+#[test]
+fn wrong_write_is_write_path() {
+    let device = SimulatedDevice::new(512 * MB).flip(flip(FlipKind::Write { count: 1 }));
+    let result = run(&device, config(3));
+    assert_eq!(result.verdict, Verdict::ErrorsFound);
+    assert_eq!(result.recorder.errors.len(), 1);
+    assert!(result.log.contains("WRITE-PATH"), "{}", result.log);
+}
+
+#[test]
+fn device_loss_stops_testing() {
+    let device = SimulatedDevice::new(512 * MB).fail_dispatch(20, DeviceFailure::Lost);
+    let result = run(&device, config(100));
+    assert_eq!(result.verdict, Verdict::DeviceLost);
+    assert_eq!(result.recorder.verdict, Some(Verdict::DeviceLost));
+}
+
+#[test]
+fn heat_map_is_reported_after_device_loss() {
+    let device = SimulatedDevice::new(512 * MB)
+        .flip(flip(FlipKind::Stuck))
+        .fail_dispatch(20, DeviceFailure::Lost);
+    let result = run(&device, config(100));
+    assert_eq!(result.verdict, Verdict::DeviceLost);
+    assert!(!result.recorder.errors.is_empty(), "{}", result.log);
+    assert!(result.log.contains("Error heat map"), "{}", result.log);
+}
+
+#[test]
+fn hang_stops_testing() {
+    let device = SimulatedDevice::new(512 * MB).fail_dispatch(20, DeviceFailure::Hang);
+    let result = run(&device, config(100));
+    assert_eq!(result.verdict, Verdict::GpuHang);
+}
+
+#[test]
+fn failed_allocation_is_retried_with_lower_size() {
+    let device = SimulatedDevice::new(900 * MB);
+    let result = run(
+        &device,
+        TestConfig {
+            max_test_bytes: 1024 * MB,
+            ..config(2)
+        },
+    );
+    assert_eq!(result.verdict, Verdict::Passed, "{}", result.log);
+    assert!(result.log.contains("Failed allocating"), "{}", result.log);
+    assert!(result.log.contains("Chosen test size"), "{}", result.log);
+}
+
+#[test]
+fn huge_allocation_is_split() {
+    let device = SimulatedDevice::new(1024 * MB).fail_allocations(1);
+    let result = run(&device, config(2));
+    assert_eq!(result.verdict, Verdict::Passed, "{}", result.log);
+    assert!(
+        result.log.contains("retrying with allocations"),
+        "{}",
+        result.log
+    );
+}
+
+#[test]
+fn worker_failures_are_returned_to_the_caller() {
+    let _lock = SESSION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let outcome = |device: &SimulatedDevice, config: TestConfig| {
+        let session = TestSession::builder().config(config).build().unwrap();
+        session
+            .run_simulated(device, std::io::sink(), &mut ())
+            .error
+    };
+    //a single window per allocation, so the failed allocation isn't retried split
+    let failed_allocation = outcome(
+        &SimulatedDevice::new(512 * MB)
+            .max_allocation_size(256 * MB)
+            .fail_allocations(1),
+        TestConfig {
+            worker: true,
+            ..config(2)
+        },
+    );
+    assert!(failed_allocation.map_or(false, |e| e.is::<AllocationFailed>()));
+    let probe = outcome(
+        &SimulatedDevice::new(512 * MB),
+        TestConfig {
+            probe: true,
+            ..config(2)
+        },
+    );
+    assert!(probe.map_or(false, |e| e.is::<ProbeSucceeded>()));
+}
+
+#[test]
+fn march_finds_stuck_bit() {
+    let device = SimulatedDevice::new(512 * MB).flip(flip(FlipKind::Stuck));
+    let result = run(
+        &device,
+        TestConfig {
+            mode: TestMode::MarchC,
+            ..config(2)
+        },
+    );
+    assert_eq!(result.verdict, Verdict::ErrorsFound);
+    assert!(result.log.contains("MARCH_C-"), "{}", result.log);
+    assert!(result.log.contains("STICKY"), "{}", result.log);
+}
+
+#[test]
+fn retention_passes_on_clean_device() {
+    let result = run(
+        &SimulatedDevice::new(512 * MB),
+        TestConfig {
+            mode: TestMode::Retention,
+            hold_duration: time::Duration::from_millis(10),
+            ..config(2)
+        },
+    );
+    assert_eq!(result.verdict, Verdict::Passed, "{}", result.log);
+}
+
+#[test]
+fn injected_write_flip_is_write_path() {
+    let fault = FaultSpec {
+        model: FaultModel::BitFlip {
+            address: SECOND_WINDOW + 0x1234,
+            bit: 5,
+        },
+        path: FaultPath::Write,
+        iteration: Some(2),
+    };
+    let result = run(
+        &SimulatedDevice::new(512 * MB),
+        TestConfig {
+            inject: Some(fault),
+            ..config(3)
+        },
+    );
+    assert_eq!(result.verdict, Verdict::ErrorsFound);
+    assert_eq!(result.recorder.modes, ["INITIAL_READ"], "{}", result.log);
+    assert_eq!(result.recorder.errors[0].0, SECOND_WINDOW + 0x1234);
+    assert!(
+        result
+            .log
+            .contains("Injecting fault flip:0x10001234:5/write@2"),
+        "{}",
+        result.log
+    );
+    assert!(result.log.contains("WRITE-PATH"), "{}", result.log);
+}
+
+#[test]
+fn injected_reread_flip_hits_only_rereads() {
+    let fault = FaultSpec {
+        model: FaultModel::BitFlip {
+            address: 0x1234,
+            bit: 5,
+        },
+        path: FaultPath::Reread,
+        iteration: Some(2),
+    };
+    let result = run(
+        &SimulatedDevice::new(512 * MB),
+        TestConfig {
+            inject: Some(fault),
+            ..config(3)
+        },
+    );
+    assert_eq!(result.verdict, Verdict::ErrorsFound);
+    assert_eq!(result.recorder.modes, ["NEXT_RE_READ"], "{}", result.log);
+    //0x1234 is the y component of the vector
+    assert!(
+        result.recorder.top_findings[0].contains("(lane 37)"),
+        "{:?}",
+        result.recorder.top_findings
+    );
+}
+
+#[test]
+fn high_address_line_is_flagged_over_windows() {
+    const GB: i64 = 1024 * MB;
+    //errors of the last two 3.2GB windows, all with bit 33 of the offset set
+    let device = (0..40).fold(SimulatedDevice::new(16 * GB), |device, k| {
+        device.flip(BitFlip {
+            address: 10 * GB + k * 0x0571_2344,
+            bit: 3,
+            from_iteration: 1,
+            kind: FlipKind::Stuck,
+        })
+    });
+    let result = run(&device, config(1));
+    assert_eq!(result.verdict, Verdict::ErrorsFound);
+    assert!(
+        result.log.contains(
+            "Biased address bit 33 (0x200000000 of test offset) over errors of 2 windows"
+        ),
+        "{}",
+        result.log
+    );
+}
+
+#[test]
+fn injected_stuck_lane_is_diagnosed() {
+    let fault = FaultSpec {
+        model: FaultModel::StuckLane {
+            lane: 37,
+            value: true,
+        },
+        path: FaultPath::Write,
+        iteration: Some(2),
+    };
+    let result = run(
+        &SimulatedDevice::new(512 * MB),
+        TestConfig {
+            inject: Some(fault),
+            confirm_reads: 0,
+            ..config(2)
+        },
+    );
+    assert_eq!(result.verdict, Verdict::ErrorsFound);
+    assert!(
+        result.recorder.top_findings[0].contains("(lane 37)"),
+        "{:?}",
+        result.recorder.top_findings
+    );
+}
+
+#[test]
+fn legacy_write_bug_hits_every_rewritten_window() {
+    //four 256MB windows, the first one is the re-read window
+    let result = run(
+        &SimulatedDevice::new(1024 * MB).max_allocation_size(256 * MB),
+        TestConfig {
+            inject: Some(FaultSpec::legacy_write_bug(2)),
+            confirm_reads: 0,
+            ..config(2)
+        },
+    );
+    assert_eq!(result.verdict, Verdict::ErrorsFound);
+    let addresses: Vec<i64> = result.recorder.errors.iter().map(|e| e.0).collect();
+    assert_eq!(
+        addresses,
+        [1, 2, 3].map(|window| window * 256 * MB + 0xADBA4),
+        "{}",
+        result.log
+    );
+}